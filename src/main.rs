@@ -3,49 +3,400 @@
 // Main
 
 mod solas;
-mod prelude {
-    pub use crate::solas::{Camera, Color, Ray};
-}
-
 use cgmath::{prelude::*, Vector3};
+use clap::Parser;
 use image::{ImageBuffer, Rgb, RgbImage};
-use random_number::random;
-use std::time::Instant;
+use std::error::Error;
+use std::fs;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use solas::*;
 
 const WIDTH: u32 = 1200;
 const HEIGHT: u32 = (1200.0 * 9.0 / 16.0) as u32;
 const SAMPLES: u16 = 20;
+const PASS_SAMPLES: u16 = 16;
+const MAX_DEPTH: u32 = 50;
+/// Depth after which paths may be ended early by Russian roulette.
+const ROULETTE_DEPTH: u32 = 4;
+const TILE_SIZE: u32 = 32;
+const CHECKPOINT_INTERVAL: f64 = 60.0;
+/// Random stream used for scene generation; render tiles use streams counting up from 0.
+const SCENE_STREAM: u64 = u64::MAX >> 1;
+
+struct RenderSettings {
+    width: u32,
+    height: u32,
+    /// The most samples any pixel gets.
+    samples: u16,
+    /// Samples added to each pixel by every progressive pass.
+    pass_samples: u16,
+    /// Relative error below which pixels stop being sampled; without one every pixel gets all
+    /// its samples.
+    threshold: Option<f64>,
+    /// Wall clock budget for the whole render.
+    time_limit: Option<Duration>,
+    max_depth: u32,
+    /// Bounces before Russian roulette starts; as deep as `max_depth`, it never does.
+    roulette_depth: u32,
+    filter: Filter,
+    /// Tone mapping and exposure for 8-bit output.
+    output: OutputTransform,
+    /// Number of render threads; the output doesn't depend on it.
+    threads: usize,
+    seed: u64,
+}
+
+impl RenderSettings {
+    fn new(width: u32, height: u32, samples: u16) -> RenderSettings {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+        RenderSettings {
+            width,
+            height,
+            samples,
+            pass_samples: PASS_SAMPLES,
+            threshold: None,
+            time_limit: None,
+            max_depth: MAX_DEPTH,
+            roulette_depth: ROULETTE_DEPTH,
+            filter: Filter::Box,
+            output: OutputTransform::default(),
+            threads,
+            seed: DEFAULT_SEED,
+        }
+    }
+
+    /// Let a scene file override the built-in defaults.
+    fn apply(&mut self, options: &RenderOptions) {
+        self.width = options.width.unwrap_or(self.width);
+        self.height = options.height.unwrap_or(self.height);
+        self.samples = options.samples.unwrap_or(self.samples);
+        self.max_depth = options.max_depth.unwrap_or(self.max_depth);
+        self.threshold = options.threshold.or(self.threshold);
+        self.time_limit = options
+            .time_limit
+            .map(Duration::from_secs_f64)
+            .or(self.time_limit);
+        self.filter = options.filter.unwrap_or(self.filter);
+        self.output.tone_map = options.tone_map.unwrap_or(self.output.tone_map);
+        self.output.exposure = options.exposure.unwrap_or(self.output.exposure);
+        self.output.white_point = options.white_point.unwrap_or(self.output.white_point);
+    }
+
+    /// The settings the rendered samples depend on, to be checked when resuming. The time
+    /// limit, thread count and output transform can all change between runs.
+    fn fingerprint(&self, scene: &str) -> String {
+        format!(
+            "scene={} width={} height={} samples={} pass_samples={} threshold={:?} \
+             max_depth={} filter={:?} seed={}",
+            scene,
+            self.width,
+            self.height,
+            self.samples,
+            self.pass_samples,
+            self.threshold,
+            self.max_depth,
+            self.filter,
+            self.seed
+        )
+    }
+}
+
+/// How a checkpoint identifies its scene: a built-in's name, which with the seed fixes it, or a
+/// hash of a scene file's contents and of the meshes and images it read, `files`. Resuming then
+/// doesn't depend on how the path is spelled, and notices when any of them has been edited.
+fn scene_identity(scene: &str, files: &[PathBuf]) -> Result<String, Box<dyn Error>> {
+    match scene {
+        "two-spheres" | "four-spheres" | "random-spheres" | "cornell-box" => Ok(scene.to_string()),
+        path => {
+            let mut contents = vec![];
+            for file in iter::once(Path::new(path)).chain(files.iter().map(PathBuf::as_path)) {
+                let bytes =
+                    fs::read(file).map_err(|error| format!("{}: {}", file.display(), error))?;
+                contents.extend(bytes);
+            }
+
+            Ok(format!("{:016x}", fnv1a(&contents)))
+        }
+    }
+}
+
+/// 64-bit FNV-1a. Unlike the standard library's hasher, it gives the same value in every build.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Render a scene with the Solas ray tracer.
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Built-in scene (two-spheres, four-spheres, random-spheres, cornell-box) or path to a
+    /// scene file
+    #[arg(short, long, default_value = "random-spheres")]
+    scene: String,
+
+    /// Image width in pixels [default: 1200, or the scene file's]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+
+    /// Image height in pixels [default: 675, or the scene file's]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    height: Option<u32>,
+
+    /// Samples per pixel, or the most any pixel gets with --threshold [default: 20, or the scene
+    /// file's]
+    #[arg(short = 'n', long, value_parser = clap::value_parser!(u16).range(1..))]
+    samples: Option<u16>,
+
+    /// Samples added to each pixel by every progressive pass
+    #[arg(long, default_value_t = PASS_SAMPLES, value_parser = clap::value_parser!(u16).range(1..))]
+    pass_samples: u16,
+
+    /// Stop sampling pixels once their estimated relative error falls below this, e.g. 0.02
+    /// [default: off, or the scene file's]
+    #[arg(long)]
+    threshold: Option<f64>,
+
+    /// Stop after this many seconds, keeping the samples taken so far [default: none, or the
+    /// scene file's]
+    #[arg(long)]
+    time_limit: Option<f64>,
+
+    /// Write the image so far to the output file every this many seconds
+    #[arg(long)]
+    preview_interval: Option<f64>,
+
+    /// Save the render's progress to this file as it goes, to resume it with --resume
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Seconds between checkpoints
+    #[arg(long, default_value_t = CHECKPOINT_INTERVAL)]
+    checkpoint_interval: f64,
+
+    /// Carry on from the checkpoint file instead of starting over. The render must have the
+    /// same scene and settings.
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+
+    /// Pixel reconstruction filter: box, tent, gaussian or mitchell [default: box, or the scene
+    /// file's]
+    #[arg(long)]
+    filter: Option<Filter>,
+
+    /// Tone mapping for 8-bit output: clamp, reinhard, extended-reinhard or aces [default: clamp,
+    /// or the scene file's]
+    #[arg(long)]
+    tone_map: Option<ToneMap>,
+
+    /// Exposure adjustment in stops for 8-bit output [default: 0, or the scene file's]
+    #[arg(long, allow_negative_numbers = true)]
+    exposure: Option<f64>,
+
+    /// Luminance mapped to white by extended-reinhard [default: 4, or the scene file's]
+    #[arg(long)]
+    white_point: Option<f64>,
+
+    /// Maximum number of bounces per path; Russian roulette ends most paths well before it
+    /// [default: 50, or the scene file's]
+    #[arg(long)]
+    max_depth: Option<u32>,
+
+    /// Where to write the image
+    #[arg(short, long, default_value = "output/image.png")]
+    output: PathBuf,
+
+    /// Image format: png, jpeg, bmp, tga, ... or exr, hdr and pfm for linear HDR output
+    /// [default: from the output extension]
+    #[arg(long)]
+    format: Option<String>,
+
+    /// Number of render threads [default: all cores]
+    #[arg(short = 'j', long, value_parser = clap::value_parser!(u32).range(1..))]
+    threads: Option<u32>,
+
+    /// Seed for the random scene layout and the render itself
+    #[arg(long, default_value_t = DEFAULT_SEED)]
+    seed: u64,
+}
 
 fn main() {
+    if let Err(error) = run(Args::parse()) {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let format = match &args.format {
+        Some(format) => OutputFormat::from_extension(format)
+            .ok_or_else(|| format!("unknown image format \"{}\"", format))?,
+        None => OutputFormat::from_path(&args.output)
+            .ok_or_else(|| format!("can't tell the image format of {}", args.output.display()))?,
+    };
+
+    let mut settings = RenderSettings::new(WIDTH, HEIGHT, SAMPLES);
+    settings.seed = args.seed;
+    settings.pass_samples = args.pass_samples;
+
+    let description = match args.scene.as_str() {
+        "two-spheres" => SceneDescription::from(two_spheres()),
+        "four-spheres" => SceneDescription::from(four_spheres()),
+        "random-spheres" => SceneDescription::from(random_spheres(settings.seed)),
+        "cornell-box" => cornell_box(),
+        path => load_scene(Path::new(path)).map_err(|error| format!("{}: {}", path, error))?,
+    };
+    settings.apply(&description.render);
+    let mut scene = description.scene;
+
+    // Command line flags take precedence over the scene file.
+    settings.width = args.width.unwrap_or(settings.width);
+    settings.height = args.height.unwrap_or(settings.height);
+    // Built when the scene was, before the size was settled; unless the scene says otherwise,
+    // match the image so it isn't stretched.
+    let aspect = settings.width as f64 / settings.height as f64;
+    scene
+        .camera
+        .set_aspect(description.aspect.unwrap_or(aspect));
+    settings.samples = args.samples.unwrap_or(settings.samples);
+    settings.max_depth = args.max_depth.unwrap_or(settings.max_depth);
+    settings.threshold = args.threshold.or(settings.threshold);
+    if settings.threshold.is_some_and(|threshold| threshold <= 0.0) {
+        return Err("the threshold must be positive".into());
+    }
+    if let Some(seconds) = args.time_limit {
+        if seconds <= 0.0 {
+            return Err("the time limit must be positive".into());
+        }
+        settings.time_limit = Some(Duration::from_secs_f64(seconds));
+    }
+    let preview_interval = match args.preview_interval {
+        Some(seconds) if seconds <= 0.0 => {
+            return Err("the preview interval must be positive".into());
+        }
+        seconds => seconds.map(Duration::from_secs_f64),
+    };
+    if args.checkpoint_interval <= 0.0 {
+        return Err("the checkpoint interval must be positive".into());
+    }
+    let checkpoint_interval = Duration::from_secs_f64(args.checkpoint_interval);
+    settings.filter = args.filter.unwrap_or(settings.filter);
+    settings.output.tone_map = args.tone_map.unwrap_or(settings.output.tone_map);
+    settings.output.exposure = args.exposure.unwrap_or(settings.output.exposure);
+    settings.output.white_point = args.white_point.unwrap_or(settings.output.white_point);
+    if settings.output.white_point <= 0.0 {
+        return Err("the white point must be positive".into());
+    }
+    if let Some(threads) = args.threads {
+        settings.threads = threads as usize;
+    }
+
+    if let Some(directory) = args.output.parent() {
+        fs::create_dir_all(directory)
+            .map_err(|error| format!("can't create {}: {}", directory.display(), error))?;
+    }
+
+    let fingerprint = settings.fingerprint(&scene_identity(&args.scene, &description.files)?);
+    let mut accumulator = match (&args.checkpoint, args.resume) {
+        (Some(path), true) => {
+            let checkpoint = Checkpoint::load(path, (settings.width, settings.height))
+                .map_err(|error| format!("can't read {}: {}", path.display(), error))?;
+            if checkpoint.settings != fingerprint {
+                return Err(format!(
+                    "{} was saved with different settings:\n  {}\nrather than:\n  {}",
+                    path.display(),
+                    checkpoint.settings,
+                    fingerprint
+                )
+                .into());
+            }
+            println!(
+                "Resuming with {:.1} samples per pixel",
+                checkpoint.accumulator.average_samples()
+            );
+
+            checkpoint.accumulator
+        }
+        _ => Accumulator::new(settings.width, settings.height),
+    };
+    let save_checkpoint = |accumulator: &Accumulator, path: &Path| {
+        let checkpoint = Checkpoint {
+            settings: fingerprint.clone(),
+            accumulator: accumulator.clone(),
+        };
+        checkpoint
+            .save(path)
+            .map_err(|error| format!("can't write {}: {}", path.display(), error))
+    };
+
     let start = Instant::now();
+    let mut last_preview = start;
+    let mut last_checkpoint = start;
+    render(&scene, &settings, &mut accumulator, |accumulator| {
+        // Neither is worth abandoning the render over; the final saves will report it again.
+        if let Some(path) = &args.checkpoint {
+            if last_checkpoint.elapsed() >= checkpoint_interval {
+                if let Err(error) = save_checkpoint(accumulator, path) {
+                    eprintln!("warning: {}", error);
+                }
+                last_checkpoint = Instant::now();
+            }
+        }
+
+        if let Some(interval) = preview_interval {
+            if last_preview.elapsed() >= interval {
+                let preview = accumulator.framebuffer();
+                if let Err(error) = preview.save(&args.output, format, &settings.output) {
+                    eprintln!("warning: can't write {}: {}", args.output.display(), error);
+                }
+                last_preview = Instant::now();
+            }
+        }
+    });
 
-    // let image = gradient_image(WIDTH, HEIGHT);
-    // let image = two_spheres(WIDTH, HEIGHT);
-    // let image = four_spheres(WIDTH, HEIGHT);
-    let image = random_spheres(WIDTH, HEIGHT);
-    
     let duration = start.elapsed();
     println!("Render time: {:?}", duration);
+    if settings.threshold.is_some() || settings.time_limit.is_some() {
+        println!(
+            "Average samples per pixel: {:.1}",
+            accumulator.average_samples()
+        );
+    }
 
-    image.save("output/image.png").unwrap();
+    // Kept after the render finishes: a render cut short by its time limit can be resumed for
+    // longer, and a finished one re-saved with a different output transform.
+    if let Some(path) = &args.checkpoint {
+        save_checkpoint(&accumulator, path)?;
+    }
+
+    accumulator
+        .framebuffer()
+        .save(&args.output, format, &settings.output)
+        .map_err(|error| format!("can't write {}: {}", args.output.display(), error))?;
+
+    Ok(())
 }
 
 fn mult(a: Rgb<f64>, b: Rgb<f64>) -> Rgb<f64> {
     Rgb([a[0] * b[0], a[1] * b[1], a[2] * b[2]])
 }
 
-fn background(ray: &Ray) -> Rgb<f64> {
-    let unit_direction = ray.direction.normalize();
-    let t = 0.5 * unit_direction.y + 1.0;
-    let lerp = (1.0 - t) * Vector3::new(1.0, 1.0, 1.0) + t * Vector3::new(0.5, 0.7, 1.0);
-
-    image::Rgb([lerp.x, lerp.y, lerp.z])
+fn add(a: Rgb<f64>, b: Rgb<f64>) -> Rgb<f64> {
+    Rgb([a[0] + b[0], a[1] + b[1], a[2] + b[2]])
 }
 
-fn color_normal(ray: &Ray, objects: &[Sphere]) -> Rgb<f64> {
-    if let Some(hit) = hit(ray, 0.001, 10000.0, &objects) {
+fn color_normal(ray: &Ray, scene: &Scene, sampler: &mut Sampler) -> Rgb<f64> {
+    if let Some(hit) = scene.world.hit(ray, 0.001, 10000.0, sampler) {
         return Rgb([
             (hit.normal.x + 1.0) / 2.0,
             (hit.normal.y + 1.0) / 2.0,
@@ -53,34 +404,146 @@ fn color_normal(ray: &Ray, objects: &[Sphere]) -> Rgb<f64> {
         ]);
     }
 
-    background(ray)
+    scene.background.color(ray)
 }
 
-fn basic_color(ray: &Ray, objects: &[Sphere], depth: i8) -> Rgb<f64> {
-    if depth < 10 {
-        if let Some(hit) = hit(ray, 0.001, 10000.0, &objects) {
-            let target = hit.p + hit.normal + random_in_unit_sphere();
-            let ray = Ray::new(hit.p, target - hit.p);
-            return color(&ray, objects, depth + 1).multiply(0.5);
+fn basic_color(
+    ray: &Ray,
+    scene: &Scene,
+    depth: u32,
+    settings: &RenderSettings,
+    sampler: &mut Sampler,
+) -> Rgb<f64> {
+    if depth < settings.max_depth {
+        if let Some(hit) = scene.world.hit(ray, 0.001, 10000.0, sampler) {
+            let target = hit.p + hit.normal + random_in_unit_sphere(sampler);
+            let ray = Ray::with_time(hit.p, target - hit.p, ray.time);
+            return color(&ray, scene, depth + 1, settings, sampler).multiply(0.5);
         }
     }
 
-    background(ray)
+    scene.background.color(ray)
 }
 
-fn color(ray: &Ray, objects: &[Sphere], depth: i8) -> Rgb<f64> {
-    if let Some(hit) = hit(ray, 0.001, 10000.0, &objects) {
-        if let Some((attenuation, scattered)) = hit.material.scatter(ray, hit) {
-            if depth < 10 {
-                let new_color = color(&scattered, objects, depth + 1);
-                return mult(attenuation.to_color(), new_color);
-            }
+fn color(
+    ray: &Ray,
+    scene: &Scene,
+    depth: u32,
+    settings: &RenderSettings,
+    sampler: &mut Sampler,
+) -> Rgb<f64> {
+    let throughput = Vector3::new(1.0, 1.0, 1.0);
+    path_color(ray, scene, depth, settings, None, throughput, sampler)
+}
+
+/// Light arriving along `ray`. Surfaces that scatter diffusely are lit both by sampling the
+/// lights directly and by their scattered rays, which find the lights too; multiple importance
+/// sampling weights the two so neither is counted twice. `scatter_pdf` is the density with
+/// which the last surface picked `ray`, if that surface was diffuse, and `throughput` is how
+/// much of the light found here reaches the camera.
+fn path_color(
+    ray: &Ray,
+    scene: &Scene,
+    depth: u32,
+    settings: &RenderSettings,
+    scatter_pdf: Option<f64>,
+    throughput: Vector3<f64>,
+    sampler: &mut Sampler,
+) -> Rgb<f64> {
+    let Some(hit) = scene.world.hit(ray, 0.001, 10000.0, sampler) else {
+        return scene.background.color(ray);
+    };
+
+    // The ray may scatter in the fog before it gets to the surface.
+    let hit = match &scene.fog {
+        Some(fog) => fog.hit(ray, hit.t, sampler).unwrap_or(hit),
+        None => hit,
+    };
+
+    let mut emitted = hit.material.emitted(&hit).to_color();
+    if let Some(scatter_pdf) = scatter_pdf {
+        if hit.material.is_emissive() {
+            let light_pdf = scene
+                .lights
+                .pdf(ray.origin, ray.direction, hit.t + 0.0001, sampler);
+            emitted = emitted.multiply(power_heuristic(scatter_pdf, light_pdf));
         }
+    }
 
-        return Rgb([0.0, 0.0, 0.0]);
+    let Some(scatter) = hit.material.scatter(ray, &hit, sampler) else {
+        return emitted;
+    };
+    if depth >= settings.max_depth {
+        return emitted;
     }
 
-    background(ray)
+    let direct = if scatter.specular {
+        Rgb([0.0, 0.0, 0.0])
+    } else {
+        direct_light(ray, &hit, scene, sampler)
+    };
+
+    // Past the first few bounces, paths that carry little light are ended at random, and the
+    // ones that go on count for more to make up for those that didn't: unbiased, but without
+    // spending deep bounces on paths that hardly contribute.
+    let mut weight = scatter.weight();
+    let mut throughput = throughput.mul_element_wise(weight);
+    if depth >= settings.roulette_depth {
+        let survival = throughput.x.max(throughput.y).max(throughput.z).min(1.0);
+        if sampler.next_f64() >= survival {
+            return add(emitted, direct);
+        }
+        weight /= survival;
+        throughput /= survival;
+    }
+
+    let scatter_pdf = (!scatter.specular).then_some(scatter.pdf);
+    let incoming = path_color(
+        &scatter.ray,
+        scene,
+        depth + 1,
+        settings,
+        scatter_pdf,
+        throughput,
+        sampler,
+    );
+    let indirect = mult(weight.to_color(), incoming);
+
+    add(emitted, add(direct, indirect))
+}
+
+/// Light a diffuse surface sends back along `ray` straight from a randomly chosen light.
+/// Anything in the way, fog included, casts a shadow.
+fn direct_light(ray: &Ray, hit: &Hit, scene: &Scene, sampler: &mut Sampler) -> Rgb<f64> {
+    let black = Rgb([0.0, 0.0, 0.0]);
+
+    let Some((light, direction, light_pdf)) = scene.lights.sample(hit.p, sampler) else {
+        return black;
+    };
+    let Some(scatter) = hit.material.evaluate(ray, hit, direction) else {
+        return black;
+    };
+    let shadow_ray = scatter.ray;
+
+    let Some(light_hit) = light.hit(&shadow_ray, 0.001, f64::INFINITY, sampler) else {
+        return black;
+    };
+    let blocked = scene
+        .world
+        .hit(&shadow_ray, 0.001, light_hit.t - 0.0001, sampler)
+        .is_some();
+    let fogged = scene
+        .fog
+        .as_ref()
+        .is_some_and(|fog| fog.hit(&shadow_ray, light_hit.t, sampler).is_some());
+    if blocked || fogged {
+        return black;
+    }
+
+    let weight = power_heuristic(light_pdf, scatter.pdf) * scatter.cosine / light_pdf;
+    let emitted = light_hit.material.emitted(&light_hit).to_color();
+
+    mult(scatter.f.to_color(), emitted).multiply(weight)
 }
 
 fn gradient(ray: Ray) -> Rgb<f64> {
@@ -116,8 +579,11 @@ fn gradient_image(width: u32, height: u32) -> RgbImage {
         aspect_ratio,
         aperture,
         focus_dist,
+        0.0,
+        0.0,
     );
 
+    let mut sampler = Sampler::new(DEFAULT_SEED, 0);
     let w = width as f64;
     let h = height as f64;
     for y in (0..height).rev() {
@@ -127,7 +593,7 @@ fn gradient_image(width: u32, height: u32) -> RgbImage {
 
             let u = (i + 0.5) / w;
             let v = (j + 0.5) / h;
-            let ray = camera.ray(u, v);
+            let ray = camera.ray(u, v, &mut sampler);
             let pixel = gradient(ray);
             let pixel = Rgb([
                 (pixel[0] * 255.0) as u8,
@@ -142,7 +608,7 @@ fn gradient_image(width: u32, height: u32) -> RgbImage {
     image
 }
 
-fn two_spheres(width: u32, height: u32) -> RgbImage {
+fn two_spheres() -> Scene {
     let look_from = Vector3::new(13.0, 2.0, 3.0);
     let look_at = Vector3::new(0.0, 0.0, 0.0);
     let vup = Vector3::new(0.0, 1.0, 0.0);
@@ -159,6 +625,8 @@ fn two_spheres(width: u32, height: u32) -> RgbImage {
         aspect_ratio,
         aperture,
         focus_dist,
+        0.0,
+        0.0,
     );
 
     let ground_material = make_lambertian(Vector3::new(0.8, 0.8, 0.0));
@@ -166,13 +634,13 @@ fn two_spheres(width: u32, height: u32) -> RgbImage {
 
     let ball_material = make_lambertian(Vector3::new(0.1, 0.1, 0.8));
     let ball = Sphere::new(Vector3::new(0.0, 0.0, -1.0), 0.5, ball_material);
-    let objects = [ground, ball];
-    let samples = SAMPLES;
-
-    trace(&objects, camera, width, height, samples)
+    let mut world = HittableList::new();
+    world.push(ground);
+    world.push(ball);
+    Scene::new(camera, world, Background::Sky)
 }
 
-fn four_spheres(width: u32, height: u32) -> RgbImage {
+fn four_spheres() -> Scene {
     let look_from = Vector3::new(0.0, 3.0, 6.0);
     let look_at = Vector3::new(0.0, 0.7, 0.0);
     let vup = Vector3::new(0.0, 1.0, 0.0);
@@ -189,6 +657,8 @@ fn four_spheres(width: u32, height: u32) -> RgbImage {
         aspect_ratio,
         aperture,
         focus_dist,
+        0.0,
+        0.0,
     );
 
     let ground_material = make_lambertian(Vector3::new(0.8, 0.8, 0.0));
@@ -203,13 +673,15 @@ fn four_spheres(width: u32, height: u32) -> RgbImage {
     let right_material = make_metal(Vector3::new(0.8, 0.6, 0.2), 0.0);
     let right = Sphere::new(Vector3::new(1.0, 0.0, -1.0), 0.5, right_material);
 
-    let objects = [ground, left, middle, right];
-    let samples = SAMPLES;
-
-    trace(&objects, camera, width, height, samples)
+    let mut world = HittableList::new();
+    world.push(ground);
+    world.push(left);
+    world.push(middle);
+    world.push(right);
+    Scene::new(camera, world, Background::Sky)
 }
 
-fn random_spheres(width: u32, height: u32) -> RgbImage {
+fn random_spheres(seed: u64) -> Scene {
     let look_from = Vector3::new(16.0, 2.0, 4.0);
     let look_at = Vector3::new(0.0, 0.0, 0.0);
     let vup = Vector3::new(0.0, 1.0, 0.0);
@@ -226,22 +698,22 @@ fn random_spheres(width: u32, height: u32) -> RgbImage {
         aspect_ratio,
         aperture,
         focus_dist,
+        0.0,
+        0.0,
     );
 
-    let mut objects: Vec<Sphere> = vec![];
+    let mut world = HittableList::new();
 
     // Ground sphere
-    objects.push(Sphere::new(
+    world.push(Sphere::new(
         Vector3::new(0.0, -1000.0, 0.0),
         1000.0,
         make_lambertian(Vector3::new(0.5, 0.5, 0.5)),
     ));
 
-    // Random spheres
-    let rand = || {
-        let x: f64 = random!();
-        x
-    };
+    // Random spheres, laid out from their own stream so the scene only depends on the seed
+    let mut sampler = Sampler::new(seed, SCENE_STREAM);
+    let mut rand = || sampler.next_f64();
 
     for x in -11..=11 {
         for y in -11..=11 {
@@ -253,7 +725,7 @@ fn random_spheres(width: u32, height: u32) -> RgbImage {
                 let material = rand();
                 if material <= 0.8 {
                     let albedo = Vector3::new(rand(), rand(), rand());
-                    objects.push(Sphere::new(center, 0.2, make_lambertian(albedo)));
+                    world.push(Sphere::new(center, 0.2, make_lambertian(albedo)));
                 } else if material <= 0.95 {
                     let albedo = Vector3::new(
                         0.5 * (1.0 + rand()),
@@ -261,94 +733,297 @@ fn random_spheres(width: u32, height: u32) -> RgbImage {
                         0.5 * (1.0 + rand()),
                     );
                     let fuzz = 0.5 * rand();
-                    objects.push(Sphere::new(center, 0.2, make_metal(albedo, fuzz)))
+                    world.push(Sphere::new(center, 0.2, make_metal(albedo, fuzz)))
                 } else {
-                    objects.push(Sphere::new(center, 0.2, make_dialectric(1.5)));
+                    world.push(Sphere::new(center, 0.2, make_dialectric(1.5)));
                 }
             } else {
-                objects.push(Sphere::new(center, 0.2, make_dialectric(1.5)));
+                world.push(Sphere::new(center, 0.2, make_dialectric(1.5)));
             }
         }
     }
 
     // Far sphere
-    objects.push(Sphere::new(
+    world.push(Sphere::new(
         Vector3::new(-4.0, 1.0, 0.0),
         1.0,
         make_lambertian(Vector3::new(0.4, 0.2, 0.1)),
     ));
 
     // middle sphere
-    objects.push(Sphere::new(
+    world.push(Sphere::new(
         Vector3::new(0.0, 1.0, 0.0),
         1.0,
         make_dialectric(1.5),
     ));
 
     // Near sphere
-    objects.push(Sphere::new(
+    world.push(Sphere::new(
         Vector3::new(4.0, 1.0, 0.0),
         1.0,
         make_metal(Vector3::new(0.7, 0.6, 0.5), 0.0),
     ));
 
-    let samples = SAMPLES;
-    trace(&objects, camera, width, height, samples)
+    Scene::new(camera, world, Background::Sky)
 }
 
-fn percent_complete(y: u32, height: u32) -> u32 {
-    let y = y as f64;
-    let height = height as f64;
+fn cornell_box() -> SceneDescription {
+    let look_from = Vector3::new(278.0, 278.0, -800.0);
+    let look_at = Vector3::new(278.0, 278.0, 0.0);
+    let vup = Vector3::new(0.0, 1.0, 0.0);
+    let focus_dist = 10.0;
+    let aspect_ratio = 1.0;
+    let vfov = 40.0;
+    let aperture = 0.0;
+
+    let camera = Camera::new(
+        look_from,
+        look_at,
+        vup,
+        vfov,
+        aspect_ratio,
+        aperture,
+        focus_dist,
+        0.0,
+        0.0,
+    );
+
+    let red = make_lambertian(Vector3::new(0.65, 0.05, 0.05));
+    let white = make_lambertian(Vector3::new(0.73, 0.73, 0.73));
+    let green = make_lambertian(Vector3::new(0.12, 0.45, 0.15));
+    let light = make_diffuse_light(Vector3::new(15.0, 15.0, 15.0));
+
+    let mut world = HittableList::new();
+    world.push(Quad::yz_rect(0.0, 555.0, 0.0, 555.0, 555.0, green));
+    world.push(Quad::yz_rect(0.0, 555.0, 0.0, 555.0, 0.0, red));
+    world.push(Quad::xz_rect(213.0, 343.0, 227.0, 332.0, 554.0, light));
+    world.push(Quad::xz_rect(0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.push(Quad::xz_rect(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+    world.push(Quad::xy_rect(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+
+    let tall_box = Cuboid::new(
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(165.0, 330.0, 165.0),
+        white.clone(),
+    );
+    world.push(
+        Instance::of(Arc::new(tall_box))
+            .rotate(Vector3::new(0.0, 1.0, 0.0), 15.0)
+            .translate(Vector3::new(265.0, 0.0, 295.0)),
+    );
+
+    let short_box = Cuboid::new(
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(165.0, 165.0, 165.0),
+        white,
+    );
+    world.push(
+        Instance::of(Arc::new(short_box))
+            .rotate(Vector3::new(0.0, 1.0, 0.0), -18.0)
+            .translate(Vector3::new(130.0, 0.0, 65.0)),
+    );
 
-    ((height - y) / height * 100.0) as u32
+    SceneDescription {
+        scene: Scene::new(
+            camera,
+            world,
+            Background::Solid(Vector3::new(0.0, 0.0, 0.0)),
+        ),
+        render: RenderOptions {
+            width: Some(600),
+            height: Some(600),
+            samples: Some(200),
+            ..Default::default()
+        },
+        aspect: None,
+        files: vec![],
+    }
 }
 
-fn trace(objects: &[Sphere], camera: Camera, width: u32, height: u32, samples: u16) -> RgbImage {
-    let mut image: RgbImage = ImageBuffer::new(width, height);
+fn percent_complete(done: u32, total: u32) -> u32 {
+    let done = done as f64;
+    let total = total as f64;
 
-    let w = width as f64;
-    let h = height as f64;
-    let progress_step = (h / 10.0) as u32;
-    for y in (0..height).rev() {
-        if y % progress_step == 0 {
-            println!("{}% complete", percent_complete(y, height));
-        }
+    (done / total * 100.0) as u32
+}
 
-        for x in 0..width {
-            let mut accumulated_color = Rgb([0.0, 0.0, 0.0]);
+/// New samples for a block of pixels, to be added to the accumulator.
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    pixels: Vec<PixelStats>,
+}
 
-            for _ in 0..samples {
-                let i = x as f64;
-                let j = y as f64;
+fn tile_count(settings: &RenderSettings) -> u32 {
+    settings.width.div_ceil(TILE_SIZE) * settings.height.div_ceil(TILE_SIZE)
+}
 
-                let u = (i + 0.5) / w;
-                let v = (j + 0.5) / h;
-                let ray = camera.ray(u, v);
+fn render_tile(
+    scene: &Scene,
+    settings: &RenderSettings,
+    accumulator: &Accumulator,
+    index: u32,
+) -> Tile {
+    let tiles_across = settings.width.div_ceil(TILE_SIZE);
+    let x0 = (index % tiles_across) * TILE_SIZE;
+    let y0 = (index / tiles_across) * TILE_SIZE;
+    let width = TILE_SIZE.min(settings.width - x0);
+    let height = TILE_SIZE.min(settings.height - y0);
+
+    // Each tile gets its own random stream for each of its passes, so the image doesn't depend
+    // on which thread happened to pick the tile up, nor on the render having been stopped and
+    // resumed. Pixels that are still being sampled all have this tile's passes' worth.
+    let samples = (y0..y0 + height)
+        .flat_map(|row| (x0..x0 + width).map(move |x| (x, row)))
+        .map(|(x, row)| accumulator.get(x, row).samples)
+        .max()
+        .unwrap_or(0);
+    let pass = samples / settings.pass_samples as u32;
+    let stream = pass as u64 * tile_count(settings) as u64 + index as u64;
+    let mut sampler = Sampler::new(settings.seed, stream);
+
+    let w = settings.width as f64;
+    let h = settings.height as f64;
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for row in y0..y0 + height {
+        let y = settings.height - row - 1;
+
+        for x in x0..x0 + width {
+            let mut stats = PixelStats::default();
+            let done = accumulator.get(x, row);
+            if settings
+                .threshold
+                .is_some_and(|threshold| done.converged(threshold))
+            {
+                pixels.push(stats);
+                continue;
+            }
 
-                let pixel = color(&ray, &objects, 1);
-                accumulated_color[0] += pixel[0];
-                accumulated_color[1] += pixel[1];
-                accumulated_color[2] += pixel[2];
+            // Sample indices carry on from the last pass, so stratification covers the whole
+            // sample budget.
+            let first = done.samples;
+            let last = (first + settings.pass_samples as u32).min(settings.samples as u32);
+            for index in first..last {
+                let (dx, dy) =
+                    settings
+                        .filter
+                        .sample_offset(index, settings.samples as u32, &mut sampler);
+                let weight = settings.filter.weight(dx, dy);
+
+                let i = x as f64 + 0.5 + dx;
+                let j = y as f64 + 0.5 + dy;
+
+                let u = i / w;
+                let v = j / h;
+                let ray = scene.camera.ray(u, v, &mut sampler);
+
+                let pixel = color(&ray, scene, 1, settings, &mut sampler);
+                stats.add(pixel, weight);
             }
 
-            let sample_portion = 1.0 / samples as f64;
-            let average_color = Rgb([
-                accumulated_color[0] * sample_portion,
-                accumulated_color[1] * sample_portion,
-                accumulated_color[2] * sample_portion
-            ]).gamma2();
+            pixels.push(stats);
+        }
+    }
 
-            let pixel = Rgb([
-                (average_color[0] * 255.0) as u8,
-                (average_color[1] * 255.0) as u8,
-                (average_color[2] * 255.0) as u8,
-            ]);
+    Tile {
+        x: x0,
+        y: y0,
+        width,
+        height,
+        pixels,
+    }
+}
 
-            image.put_pixel(x, height - y - 1, pixel);
+/// Renders progressively, in passes of `settings.pass_samples` samples per pixel, until every
+/// pixel has all its samples or has converged, or time runs out. `on_pass` is shown the image
+/// after each pass.
+///
+/// A pass cut short by the time limit leaves some tiles a pass behind the others; they catch up
+/// when the render is resumed, so passes carry on until every pixel is finished rather than for
+/// a fixed count.
+fn render(
+    scene: &Scene,
+    settings: &RenderSettings,
+    accumulator: &mut Accumulator,
+    mut on_pass: impl FnMut(&Accumulator),
+) {
+    assert!(settings.threads > 0, "rendering needs at least one thread");
+    let deadline = settings.time_limit.map(|limit| Instant::now() + limit);
+    let tile_count = tile_count(settings);
+    let passes = (settings.samples as u32).div_ceil(settings.pass_samples.max(1) as u32);
+    let first_pass = accumulator.average_samples() as u32 / settings.pass_samples.max(1) as u32;
+    let mut last_percent = 0;
+
+    for pass in first_pass.. {
+        if accumulator.finished(settings.samples as u32, settings.threshold) {
+            break;
+        }
+
+        // Every run finishes at least one pass, so no pixel is left without samples.
+        let out_of_time = move || {
+            pass > first_pass && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        };
+        let next_tile = AtomicU32::new(0);
+        let (sender, receiver) = mpsc::channel();
+        let shared = &*accumulator;
+
+        let tiles: Vec<Tile> = thread::scope(|scope| {
+            for _ in 0..settings.threads {
+                let sender = sender.clone();
+                let next_tile = &next_tile;
+
+                scope.spawn(move || loop {
+                    let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                    if index >= tile_count || out_of_time() {
+                        break;
+                    }
+
+                    sender
+                        .send(render_tile(scene, settings, shared, index))
+                        .unwrap();
+                });
+            }
+            drop(sender);
+
+            receiver
+                .iter()
+                .enumerate()
+                .map(|(done, tile)| {
+                    let done = pass * tile_count + done as u32 + 1;
+                    let percent = percent_complete(done, passes * tile_count).min(100);
+                    if percent / 10 > last_percent / 10 {
+                        println!("{}% complete", percent);
+                        last_percent = percent;
+                    }
+
+                    tile
+                })
+                .collect()
+        });
+
+        for tile in tiles {
+            let mut pixels = tile.pixels.iter();
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width {
+                    accumulator.merge(x, y, pixels.next().unwrap());
+                }
+            }
+        }
+        on_pass(accumulator);
+
+        if out_of_time() {
+            break;
         }
     }
+}
 
-    image
+fn trace(scene: &Scene, settings: &RenderSettings) -> Framebuffer {
+    let mut accumulator = Accumulator::new(settings.width, settings.height);
+    render(scene, settings, &mut accumulator, |_| {});
+
+    accumulator.framebuffer()
 }
 
 #[cfg(test)]
@@ -362,4 +1037,105 @@ mod tests {
 
         assert_eq!(color[0], 0.25);
     }
+
+    #[test]
+    fn scene_files_are_identified_by_contents() {
+        let pyramid = load_scene(Path::new("scenes/pyramid.toml")).unwrap();
+        assert_eq!(pyramid.files, [PathBuf::from("scenes/pyramid.obj")]);
+        let identity = |scene| scene_identity(scene, &pyramid.files).unwrap();
+
+        assert_eq!(
+            identity("./scenes/pyramid.toml"),
+            identity("scenes/pyramid.toml")
+        );
+        assert_ne!(
+            identity("scenes/four_spheres.toml"),
+            identity("scenes/pyramid.toml")
+        );
+        // The mesh counts as part of the scene.
+        assert_ne!(
+            scene_identity("scenes/pyramid.toml", &[]).unwrap(),
+            identity("scenes/pyramid.toml")
+        );
+        assert_eq!(scene_identity("cornell-box", &[]).unwrap(), "cornell-box");
+        assert!(scene_identity("scenes/missing.toml", &[]).is_err());
+    }
+
+    #[test]
+    fn trace_is_independent_of_thread_count() {
+        let mut single = RenderSettings::new(80, 45, 4);
+        single.threads = 1;
+        let mut parallel = RenderSettings::new(80, 45, 4);
+        parallel.threads = 4;
+
+        let scene = four_spheres();
+        assert_eq!(trace(&scene, &single), trace(&scene, &parallel));
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted() {
+        let scene = four_spheres();
+        let settings = RenderSettings::new(80, 45, 48);
+
+        // Snapshot, as a checkpoint would be, after the first pass.
+        let mut uninterrupted = Accumulator::new(80, 45);
+        let mut checkpoint = None;
+        render(&scene, &settings, &mut uninterrupted, |accumulator| {
+            if checkpoint.is_none() {
+                let mut bytes = vec![];
+                accumulator.write(&mut bytes).unwrap();
+                checkpoint = Some(bytes);
+            }
+        });
+
+        let mut resumed = Accumulator::read(&mut checkpoint.unwrap().as_slice(), (80, 45)).unwrap();
+        assert_eq!(resumed.average_samples(), PASS_SAMPLES as f64);
+        render(&scene, &settings, &mut resumed, |_| {});
+        assert_eq!(resumed, uninterrupted);
+    }
+
+    #[test]
+    fn russian_roulette_is_unbiased() {
+        let scene = cornell_box().scene;
+        let mean = |roulette_depth| {
+            let mut settings = RenderSettings::new(16, 16, 256);
+            settings.roulette_depth = roulette_depth;
+            let image = trace(&scene, &settings);
+            let mut total = 0.0;
+            for y in 0..image.height {
+                for x in 0..image.width {
+                    total += image.get(x, y).0.iter().sum::<f64>();
+                }
+            }
+
+            total / (3 * image.width * image.height) as f64
+        };
+
+        // Roulette from the first bounce, to give it every chance to go wrong.
+        let exact = mean(MAX_DEPTH);
+        let roulette = mean(1);
+        assert!((roulette - exact).abs() < 0.02 * exact);
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_converged_pixels_early() {
+        let scene = four_spheres();
+        let mut settings = RenderSettings::new(80, 45, 64);
+        settings.threshold = Some(0.05);
+
+        let mut passes = 0;
+        let mut accumulator = Accumulator::new(80, 45);
+        render(&scene, &settings, &mut accumulator, |_| passes += 1);
+
+        // The sky converges in the first pass; the spheres' edges need more.
+        let average = accumulator.average_samples();
+        assert!(average > PASS_SAMPLES as f64 && average < 64.0);
+        assert!(passes >= 2);
+
+        // Skipping pixels mustn't depend on the thread count either.
+        settings.threads = 1;
+        let mut single = Accumulator::new(80, 45);
+        render(&scene, &settings, &mut single, |_| {});
+        assert_eq!(single, accumulator);
+    }
 }
@@ -0,0 +1,92 @@
+/// Axis-aligned bounding box
+use super::Ray;
+use cgmath::Vector3;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Vector3<f64>,
+    pub max: Vector3<f64>,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f64>, max: Vector3<f64>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// A box containing nothing, useful as the starting point when surrounding a set of boxes.
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vector3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vector3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    /// Grows any side thinner than `delta` so flat objects still have a box rays can hit.
+    pub fn pad(&self, delta: f64) -> Aabb {
+        let mut min = self.min;
+        let mut max = self.max;
+
+        for axis in 0..3 {
+            if max[axis] - min[axis] < delta {
+                min[axis] -= delta / 2.0;
+                max[axis] += delta / 2.0;
+            }
+        }
+
+        Aabb { min, max }
+    }
+
+    pub fn centroid(&self) -> Vector3<f64> {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Index of the axis (0 = x, 1 = y, 2 = z) along which the box is largest.
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test: does the ray pass through the box anywhere between `min` and `max`?
+    pub fn hit(&self, ray: &Ray, mut min: f64, mut max: f64) -> bool {
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inv_d;
+
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            min = t0.max(min);
+            max = t1.min(max);
+
+            if max < min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
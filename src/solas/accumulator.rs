@@ -0,0 +1,243 @@
+// Progressive sample accumulation
+
+use super::{Framebuffer, RgbExt};
+use image::Rgb;
+use std::io::{self, Read, Write};
+
+/// Pixels aren't judged converged before they have this many samples; the variance of fewer
+/// is too unreliable to stop on.
+pub const MIN_ADAPTIVE_SAMPLES: u32 = 16;
+
+/// Luminance below which pixels are judged as if they were this bright. Without it, nearly
+/// black pixels would need an absurd number of samples to reach a relative error.
+const DARK_LUMINANCE: f64 = 0.1;
+
+/// Running totals for one pixel: enough to resolve its color and to estimate how noisy that
+/// color still is.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PixelStats {
+    /// Filter weighted sum of the sample colors, and the sum of the weights.
+    color: [f64; 3],
+    weight: f64,
+    pub samples: u32,
+    /// Sums of the samples' luminance and its square, for the variance.
+    luminance: f64,
+    luminance_squared: f64,
+}
+
+impl PixelStats {
+    pub fn add(&mut self, color: Rgb<f64>, weight: f64) {
+        for (total, channel) in self.color.iter_mut().zip(color.0) {
+            *total += channel * weight;
+        }
+        self.weight += weight;
+        self.samples += 1;
+
+        let luminance = color.luminance();
+        self.luminance += luminance;
+        self.luminance_squared += luminance * luminance;
+    }
+
+    /// Adds in samples accumulated separately, e.g. by a later pass.
+    pub fn merge(&mut self, other: &PixelStats) {
+        for (total, channel) in self.color.iter_mut().zip(other.color) {
+            *total += channel;
+        }
+        self.weight += other.weight;
+        self.samples += other.samples;
+        self.luminance += other.luminance;
+        self.luminance_squared += other.luminance_squared;
+    }
+
+    /// The pixel's color so far. Filters with negative lobes can push it below zero, so it's
+    /// clamped there.
+    pub fn resolve(&self) -> Rgb<f64> {
+        if self.weight <= 0.0 {
+            return Rgb([0.0, 0.0, 0.0]);
+        }
+
+        Rgb(self.color.map(|total| (total / self.weight).max(0.0)))
+    }
+
+    /// Standard error of the mean luminance, relative to the mean. Infinite until there are
+    /// two samples to estimate it from.
+    pub fn relative_error(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+
+        let n = self.samples as f64;
+        let mean = self.luminance / n;
+        let variance = ((self.luminance_squared - n * mean * mean) / (n - 1.0)).max(0.0);
+
+        (variance / n).sqrt() / mean.max(DARK_LUMINANCE)
+    }
+
+    /// Whether the pixel has enough samples that more aren't worth taking.
+    pub fn converged(&self, threshold: f64) -> bool {
+        self.samples >= MIN_ADAPTIVE_SAMPLES && self.relative_error() < threshold
+    }
+}
+
+/// Every pixel's running totals, which successive passes of samples are added to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accumulator {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<PixelStats>,
+}
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32) -> Accumulator {
+        Accumulator {
+            width,
+            height,
+            pixels: vec![PixelStats::default(); (width * height) as usize],
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> &PixelStats {
+        &self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn merge(&mut self, x: u32, y: u32, stats: &PixelStats) {
+        self.pixels[(y * self.width + x) as usize].merge(stats);
+    }
+
+    /// Whether every pixel has all `samples` of its samples, or has converged to `threshold`.
+    pub fn finished(&self, samples: u32, threshold: Option<f64>) -> bool {
+        self.pixels
+            .iter()
+            .all(|pixel| pixel.samples >= samples || threshold.is_some_and(|t| pixel.converged(t)))
+    }
+
+    pub fn average_samples(&self) -> f64 {
+        let total: u64 = self.pixels.iter().map(|pixel| pixel.samples as u64).sum();
+
+        total as f64 / self.pixels.len().max(1) as f64
+    }
+
+    /// The image as it stands.
+    pub fn framebuffer(&self) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                framebuffer.set(x, y, self.get(x, y).resolve());
+            }
+        }
+
+        framebuffer
+    }
+
+    /// Every total as raw little-endian bits, so that an accumulator read back carries on
+    /// exactly where this one left off.
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.width.to_le_bytes())?;
+        out.write_all(&self.height.to_le_bytes())?;
+
+        for pixel in &self.pixels {
+            for total in pixel.color {
+                out.write_all(&total.to_le_bytes())?;
+            }
+            out.write_all(&pixel.weight.to_le_bytes())?;
+            out.write_all(&pixel.samples.to_le_bytes())?;
+            out.write_all(&pixel.luminance.to_le_bytes())?;
+            out.write_all(&pixel.luminance_squared.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads back what `write` wrote, which must be for a `width` by `height` image. The size is
+    /// checked before any pixels are allocated, so a corrupt header can't ask for a huge image.
+    pub fn read(input: &mut impl Read, (width, height): (u32, u32)) -> io::Result<Accumulator> {
+        let size = (read_u32(input)?, read_u32(input)?);
+        if size != (width, height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "image size is {}x{} rather than {}x{}",
+                    size.0, size.1, width, height
+                ),
+            ));
+        }
+
+        let mut accumulator = Accumulator::new(width, height);
+
+        for pixel in &mut accumulator.pixels {
+            pixel.color = [read_f64(input)?, read_f64(input)?, read_f64(input)?];
+            pixel.weight = read_f64(input)?;
+            pixel.samples = read_u32(input)?;
+            pixel.luminance = read_f64(input)?;
+            pixel.luminance_squared = read_f64(input)?;
+        }
+
+        Ok(accumulator)
+    }
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f64(input: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+
+    Ok(f64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merging_matches_adding() {
+        let samples = [
+            (Rgb([1.0, 0.5, 0.0]), 1.0),
+            (Rgb([0.2, 0.4, 0.6]), 0.5),
+            (Rgb([0.0, 0.0, 3.0]), 0.25),
+        ];
+
+        let mut all = PixelStats::default();
+        let mut first = PixelStats::default();
+        let mut rest = PixelStats::default();
+        for (i, (color, weight)) in samples.into_iter().enumerate() {
+            all.add(color, weight);
+            if i == 0 {
+                first.add(color, weight);
+            } else {
+                rest.add(color, weight);
+            }
+        }
+        first.merge(&rest);
+
+        assert_eq!(first.samples, 3);
+        assert!((0..3).all(|c| (first.resolve()[c] - all.resolve()[c]).abs() < 1e-12));
+        assert!((first.relative_error() - all.relative_error()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn constant_pixels_converge_and_noisy_ones_do_not() {
+        let mut constant = PixelStats::default();
+        let mut noisy = PixelStats::default();
+        for i in 0..MIN_ADAPTIVE_SAMPLES {
+            constant.add(Rgb([0.5, 0.5, 0.5]), 1.0);
+            let value = if i % 2 == 0 { 0.0 } else { 1.0 };
+            noisy.add(Rgb([value, value, value]), 1.0);
+        }
+
+        assert!(constant.relative_error() < 1e-6);
+        assert!(constant.converged(0.01));
+        assert!(!noisy.converged(0.01));
+
+        // Not before the minimum number of samples, however clean they look.
+        let mut early = PixelStats::default();
+        early.add(Rgb([0.5, 0.5, 0.5]), 1.0);
+        early.add(Rgb([0.5, 0.5, 0.5]), 1.0);
+        assert!(!early.converged(0.01));
+    }
+}
@@ -0,0 +1,201 @@
+// Bounding Volume Hierarchy
+
+use super::*;
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+enum Node {
+    Leaf(Box<dyn Hittable>),
+    Branch {
+        left: Box<Node>,
+        right: Box<Node>,
+        bounds: Aabb,
+    },
+}
+
+impl Node {
+    fn build(mut objects: Vec<Box<dyn Hittable>>) -> Node {
+        if objects.len() == 1 {
+            return Node::Leaf(objects.pop().unwrap());
+        }
+
+        // Split at the median centroid along the axis where the centroids are most spread out.
+        let centroid_bounds = objects.iter().fold(Aabb::empty(), |bounds, object| {
+            let centroid = object.bounding_box().centroid();
+            bounds.surrounding(&Aabb::new(centroid, centroid))
+        });
+        let axis = centroid_bounds.longest_axis();
+
+        objects.sort_by(|a, b| {
+            let a = a.bounding_box().centroid()[axis];
+            let b = b.bounding_box().centroid()[axis];
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        });
+
+        let right = objects.split_off(objects.len() / 2);
+        let left = Node::build(objects);
+        let right = Node::build(right);
+        let bounds = left.bounding_box().surrounding(&right.bounding_box());
+
+        Node::Branch {
+            left: Box::new(left),
+            right: Box::new(right),
+            bounds,
+        }
+    }
+
+    fn hit(&self, ray: &Ray, min: f64, max: f64, sampler: &mut Sampler) -> Option<Hit<'_>> {
+        match self {
+            Node::Leaf(object) => object.hit(ray, min, max, sampler),
+            Node::Branch {
+                left,
+                right,
+                bounds,
+            } => {
+                if !bounds.hit(ray, min, max) {
+                    return None;
+                }
+
+                // Anything on the right has to be closer than what we found on the left.
+                let left_hit = left.hit(ray, min, max, sampler);
+                let max = left_hit.map_or(max, |hit| hit.t);
+
+                right.hit(ray, min, max, sampler).or(left_hit)
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            Node::Leaf(object) => object.bounding_box(),
+            Node::Branch { bounds, .. } => *bounds,
+        }
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        match self {
+            Node::Leaf(object) => object.collect_lights(lights),
+            Node::Branch { left, right, .. } => {
+                left.collect_lights(lights);
+                right.collect_lights(lights);
+            }
+        }
+    }
+}
+
+/// Acceleration structure built once per scene, replacing the linear scan over every object.
+pub struct Bvh {
+    root: Option<Node>,
+}
+
+impl Bvh {
+    pub fn new(world: HittableList) -> Bvh {
+        let root = if world.is_empty() {
+            None
+        } else {
+            Some(Node::build(world.objects))
+        };
+
+        Bvh { root }
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, ray: &Ray, min: f64, max: f64, sampler: &mut Sampler) -> Option<Hit<'_>> {
+        self.root
+            .as_ref()
+            .and_then(|root| root.hit(ray, min, max, sampler))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.root
+            .as_ref()
+            .map_or(Aabb::empty(), |root| root.bounding_box())
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        if let Some(root) = &self.root {
+            root.collect_lights(lights);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    fn random_world(spheres: &[(Vector3<f64>, f64)]) -> HittableList {
+        let mut world = HittableList::new();
+        for (center, radius) in spheres {
+            world.push(Sphere::new(
+                *center,
+                *radius,
+                make_lambertian(Vector3::new(0.5, 0.5, 0.5)),
+            ));
+        }
+
+        world
+    }
+
+    #[test]
+    fn bvh_matches_brute_force() {
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+        let point_in = |sampler: &mut Sampler, size: Vector3<f64>| {
+            Vector3::new(
+                (sampler.next_f64() - 0.5) * size.x,
+                sampler.next_f64() * size.y,
+                (sampler.next_f64() - 0.5) * size.z,
+            )
+        };
+
+        let spheres: Vec<(Vector3<f64>, f64)> = (0..500)
+            .map(|_| {
+                let center = point_in(&mut sampler, Vector3::new(20.0, 4.0, 20.0));
+                (center, 0.1 + sampler.next_f64() * 0.5)
+            })
+            .collect();
+
+        let brute_force = random_world(&spheres);
+        let bvh = Bvh::new(random_world(&spheres));
+
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let origin = point_in(&mut sampler, Vector3::new(30.0, 10.0, 30.0));
+            let direction = random_in_unit_sphere(&mut sampler);
+            let ray = Ray::new(origin, direction);
+
+            let expected = intersections::hit(
+                &ray,
+                0.001,
+                10000.0,
+                &brute_force.objects,
+                &mut Sampler::new(DEFAULT_SEED, 0),
+            );
+            let actual = bvh.hit(&ray, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0));
+
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(expected), Some(actual)) => {
+                    hits += 1;
+                    assert_eq!(expected.t, actual.t);
+                    assert_eq!(expected.p, actual.p);
+                    assert_eq!(expected.normal, actual.normal);
+                }
+                _ => panic!("BVH and brute force disagree on whether the ray hits"),
+            }
+        }
+
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn empty_bvh_never_hits() {
+        let bvh = Bvh::new(HittableList::new());
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0));
+
+        assert!(bvh
+            .hit(&ray, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+            .is_none());
+    }
+}
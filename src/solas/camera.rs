@@ -1,6 +1,6 @@
+use super::Sampler;
 /// Camera
 use cgmath::{prelude::*, Vector3};
-use random_number::random;
 
 pub struct Camera {
     pub origin: Vector3<f64>,
@@ -11,11 +11,15 @@ pub struct Camera {
     pub v: Vector3<f64>,
     pub w: Vector3<f64>,
     pub lens_radius: f64,
+    /// Rays are spread evenly over the time the shutter is open, to blur moving objects.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
 }
 
-fn random_in_unit_disk() -> Vector3<f64> {
+fn random_in_unit_disk(sampler: &mut Sampler) -> Vector3<f64> {
     loop {
-        let p: Vector3::<f64> = 2.0 * Vector3::new(random!(), random!(), random!());
+        let p: Vector3<f64> =
+            2.0 * Vector3::new(sampler.next_f64(), sampler.next_f64(), sampler.next_f64());
 
         if p.dot(p) < 1.0 {
             return p;
@@ -24,6 +28,7 @@ fn random_in_unit_disk() -> Vector3<f64> {
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         look_from: Vector3<f64>,
         look_at: Vector3<f64>,
@@ -32,6 +37,8 @@ impl Camera {
         aspect: f64,
         aperture: f64,
         focus_dist: f64,
+        shutter_open: f64,
+        shutter_close: f64,
     ) -> Camera {
         let lens_radius = aperture / 2.0;
         let theta = vfov * std::f64::consts::PI / 180.0;
@@ -55,16 +62,65 @@ impl Camera {
             v,
             w,
             lens_radius,
+            shutter_open,
+            shutter_close,
         }
     }
 
-    pub fn ray(&self, s: f64, t: f64) -> super::Ray {
-        let rd = self.lens_radius * random_in_unit_disk();
+    /// Widens or narrows the view to `aspect`, width over height, keeping its height and where
+    /// it's centered.
+    pub fn set_aspect(&mut self, aspect: f64) {
+        let center = self.lower_left + 0.5 * (self.horizontal + self.vertical);
+        self.horizontal = aspect * self.vertical.magnitude() * self.u;
+        self.lower_left = center - 0.5 * (self.horizontal + self.vertical);
+    }
+
+    pub fn ray(&self, s: f64, t: f64, sampler: &mut Sampler) -> super::Ray {
+        let rd = self.lens_radius * random_in_unit_disk(sampler);
         let offset = self.u * rd.x + self.v * rd.y;
-        
-        super::Ray {
-            origin: self.origin + offset,
-            direction: self.lower_left + s * self.horizontal + t*self.vertical - self.origin - offset,
-        }
+
+        // Only draw a time when there's a shutter interval, so still images use the same random
+        // numbers as before.
+        let time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + (self.shutter_close - self.shutter_open) * sampler.next_f64()
+        } else {
+            self.shutter_open
+        };
+
+        super::Ray::with_time(
+            self.origin + offset,
+            self.lower_left + s * self.horizontal + t * self.vertical - self.origin - offset,
+            time,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(aspect: f64) -> Camera {
+        Camera::new(
+            Vector3::new(13.0, 2.0, 3.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            20.0,
+            aspect,
+            0.1,
+            10.0,
+            0.0,
+            0.0,
+        )
+    }
+
+    #[test]
+    fn changing_aspect_matches_building_with_it() {
+        let mut square = camera(16.0 / 9.0);
+        square.set_aspect(1.0);
+        let expected = camera(1.0);
+
+        assert!((square.lower_left - expected.lower_left).magnitude() < 1e-12);
+        assert!((square.horizontal - expected.horizontal).magnitude() < 1e-12);
+        assert!((square.vertical - expected.vertical).magnitude() < 1e-12);
     }
 }
@@ -0,0 +1,111 @@
+// Render checkpoints
+//
+// A checkpoint is the accumulator as of the end of a pass. The random stream for each of a tile's
+// passes is derived from the seed, the tile and how many samples its pixels already have, so the
+// accumulator holds all the random state there is: a render resumed from a checkpoint comes out
+// exactly as if it had never stopped.
+
+use super::Accumulator;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"SOLASCKP";
+const VERSION: u32 = 1;
+
+pub struct Checkpoint {
+    /// Everything besides the scene that the result depends on, written out so resuming with
+    /// different settings can be refused.
+    pub settings: String,
+    pub accumulator: Accumulator,
+}
+
+impl Checkpoint {
+    /// Writes to a temporary file first, so being interrupted mid-save doesn't lose the last
+    /// checkpoint.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temporary = path.with_extension("partial");
+        let mut file = BufWriter::new(File::create(&temporary)?);
+        self.write(&mut file)?;
+        file.into_inner()?.sync_all()?;
+
+        fs::rename(&temporary, path)
+    }
+
+    /// Loads a checkpoint of a `(width, height)` render.
+    pub fn load(path: &Path, size: (u32, u32)) -> io::Result<Checkpoint> {
+        Checkpoint::read(&mut BufReader::new(File::open(path)?), size)
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(self.settings.len() as u32).to_le_bytes())?;
+        out.write_all(self.settings.as_bytes())?;
+
+        self.accumulator.write(out)
+    }
+
+    fn read(input: &mut impl Read, size: (u32, u32)) -> io::Result<Checkpoint> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a checkpoint file"));
+        }
+
+        let mut word = [0; 4];
+        input.read_exact(&mut word)?;
+        if u32::from_le_bytes(word) != VERSION {
+            return Err(invalid("checkpoint is from an incompatible version"));
+        }
+
+        input.read_exact(&mut word)?;
+        let mut settings = vec![];
+        input
+            .take(u32::from_le_bytes(word) as u64)
+            .read_to_end(&mut settings)?;
+        let settings = String::from_utf8(settings).map_err(|_| invalid("corrupt checkpoint"))?;
+
+        let accumulator = Accumulator::read(input, size)?;
+
+        Ok(Checkpoint {
+            settings,
+            accumulator,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solas::PixelStats;
+    use image::Rgb;
+
+    #[test]
+    fn round_trip() {
+        let mut accumulator = Accumulator::new(3, 2);
+        let mut stats = PixelStats::default();
+        stats.add(Rgb([0.1, 0.2, 0.3]), 0.7);
+        stats.add(Rgb([5.0, 0.0, 1.0 / 3.0]), 1.0);
+        accumulator.merge(1, 1, &stats);
+
+        let checkpoint = Checkpoint {
+            settings: "samples=64 seed=1".to_string(),
+            accumulator,
+        };
+        let mut bytes = vec![];
+        checkpoint.write(&mut bytes).unwrap();
+
+        let read = Checkpoint::read(&mut bytes.as_slice(), (3, 2)).unwrap();
+        assert_eq!(read.settings, checkpoint.settings);
+        assert_eq!(read.accumulator, checkpoint.accumulator);
+
+        assert!(Checkpoint::read(&mut bytes.as_slice(), (2, 3)).is_err());
+
+        bytes.truncate(bytes.len() - 1);
+        assert!(Checkpoint::read(&mut bytes.as_slice(), (3, 2)).is_err());
+        assert!(Checkpoint::read(&mut &b"not a checkpoint"[..], (3, 2)).is_err());
+    }
+}
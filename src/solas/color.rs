@@ -1,4 +1,4 @@
-/// Color.rs
+// Color.rs
 
 pub struct Color {
     r: i16,
@@ -9,11 +9,6 @@ pub struct Color {
 
 impl Color {
     pub fn new(r: i16, g: i16, b: i16) -> Color {
-        Color {
-            r: r,
-            g: g,
-            b: b,
-            a: 255,
-        }
+        Color { r, g, b, a: 255 }
     }
 }
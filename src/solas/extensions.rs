@@ -3,7 +3,30 @@ use image::Rgb;
 
 pub trait RgbExt {
     fn multiply(&self, x: f64) -> Self;
-    fn gamma2(&self) -> Self;
+    /// Relative luminance of a linear color (Rec. 709 primaries, as sRGB uses).
+    fn luminance(&self) -> f64;
+    /// The sRGB transfer function (OETF): linear [0, 1] to encoded [0, 1].
+    fn linear_to_srgb(&self) -> Self;
+    /// The inverse: encoded sRGB [0, 1] back to linear.
+    fn srgb_to_linear(&self) -> Self;
+}
+
+fn linear_to_srgb(x: f64) -> f64 {
+    let x = x.clamp(0.0, 1.0);
+
+    if x <= 0.003_130_8 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn srgb_to_linear(x: f64) -> f64 {
+    if x <= 0.040_45 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
 }
 
 impl RgbExt for Rgb<f64> {
@@ -11,12 +34,24 @@ impl RgbExt for Rgb<f64> {
         Rgb([self[0] * x, self[1] * x, self[2] * x])
     }
 
-    fn gamma2(&self) -> Self {
-        let red = self[0].sqrt();
-        let green = self[1].sqrt();
-        let blue = self[2].sqrt();
+    fn luminance(&self) -> f64 {
+        0.2126 * self[0] + 0.7152 * self[1] + 0.0722 * self[2]
+    }
+
+    fn linear_to_srgb(&self) -> Self {
+        Rgb([
+            linear_to_srgb(self[0]),
+            linear_to_srgb(self[1]),
+            linear_to_srgb(self[2]),
+        ])
+    }
 
-        Rgb([red, green, blue])
+    fn srgb_to_linear(&self) -> Self {
+        Rgb([
+            srgb_to_linear(self[0]),
+            srgb_to_linear(self[1]),
+            srgb_to_linear(self[2]),
+        ])
     }
 }
 
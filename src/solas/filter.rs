@@ -0,0 +1,147 @@
+// Pixel reconstruction filters
+
+use super::Sampler;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Weights the samples taken around a pixel center when averaging them into the pixel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    /// Every sample inside the pixel counts equally.
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    /// Mitchell-Netravali with B = C = 1/3.
+    Mitchell,
+}
+
+impl Filter {
+    /// How far from the pixel center, in pixels, samples are taken.
+    pub fn radius(&self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+        }
+    }
+
+    /// Weight of a sample `(x, y)` pixels away from the pixel center.
+    pub fn weight(&self, x: f64, y: f64) -> f64 {
+        self.weight_1d(x) * self.weight_1d(y)
+    }
+
+    fn weight_1d(&self, x: f64) -> f64 {
+        let radius = self.radius();
+        let x = x.abs();
+        if x > radius {
+            return 0.0;
+        }
+
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => 1.0 - x / radius,
+            Filter::Gaussian => {
+                let alpha = 2.0;
+                let gaussian = |x: f64| (-alpha * x * x).exp();
+
+                // Shifted down so the weight reaches zero at the edge of the filter.
+                (gaussian(x) - gaussian(radius)).max(0.0)
+            }
+            Filter::Mitchell => {
+                let b = 1.0 / 3.0;
+                let c = 1.0 / 3.0;
+
+                let weight = if x < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                        + (6.0 - 2.0 * b)
+                } else {
+                    (-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x.powi(2)
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c)
+                };
+
+                weight / 6.0
+            }
+        }
+    }
+
+    /// Offset from the pixel center for sample `index` of `samples`. As many samples as fit in a
+    /// square grid are stratified across the filter's footprint, jittered within their cell;
+    /// any left over are placed uniformly at random.
+    pub fn sample_offset(&self, index: u32, samples: u32, sampler: &mut Sampler) -> (f64, f64) {
+        let strata = (samples as f64).sqrt() as u32;
+
+        let (x, y) = if index < strata * strata {
+            let cell_x = (index % strata) as f64;
+            let cell_y = (index / strata) as f64;
+            let strata = strata as f64;
+
+            (
+                (cell_x + sampler.next_f64()) / strata,
+                (cell_y + sampler.next_f64()) / strata,
+            )
+        } else {
+            (sampler.next_f64(), sampler.next_f64())
+        };
+
+        let radius = self.radius();
+        ((2.0 * x - 1.0) * radius, (2.0 * y - 1.0) * radius)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "box" => Ok(Filter::Box),
+            "tent" => Ok(Filter::Tent),
+            "gaussian" => Ok(Filter::Gaussian),
+            "mitchell" => Ok(Filter::Mitchell),
+            _ => Err(format!(
+                "unknown filter \"{}\" (expected box, tent, gaussian or mitchell)",
+                name
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solas::DEFAULT_SEED;
+
+    #[test]
+    fn weights_vanish_at_radius() {
+        for filter in [Filter::Tent, Filter::Gaussian, Filter::Mitchell] {
+            let radius = filter.radius();
+
+            assert!(filter.weight(0.0, 0.0) > 0.0);
+            assert!(filter.weight(radius, 0.0).abs() < 1e-12);
+            assert_eq!(filter.weight(radius + 0.01, 0.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn stratified_offsets_cover_every_cell() {
+        let filter = Filter::Box;
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+        let mut cells = [false; 16];
+
+        for index in 0..16 {
+            let (x, y) = filter.sample_offset(index, 16, &mut sampler);
+            assert!(x.abs() <= 0.5 && y.abs() <= 0.5);
+
+            let cell_x = ((x + 0.5) * 4.0) as usize;
+            let cell_y = ((y + 0.5) * 4.0) as usize;
+            cells[cell_y * 4 + cell_x] = true;
+        }
+
+        assert!(cells.iter().all(|covered| *covered));
+    }
+}
@@ -0,0 +1,172 @@
+// Framebuffer and image output
+
+use super::OutputTransform;
+use image::codecs::hdr::HdrEncoder;
+use image::{ImageBuffer, ImageError, ImageFormat, Rgb, RgbImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// The kinds of file a render can be saved as. The HDR formats store the framebuffer's linear
+/// values as they are; everything else is 8-bit.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// PNG, JPEG, BMP, TGA, ... through the `image` crate.
+    Ldr(ImageFormat),
+    /// OpenEXR, as 32-bit floats.
+    Exr,
+    /// Radiance RGBE.
+    Hdr,
+    /// Portable float map.
+    Pfm,
+}
+
+impl OutputFormat {
+    /// Looks the format up by file extension (or name), e.g. "png" or "exr".
+    pub fn from_extension(extension: &str) -> Option<OutputFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "exr" => Some(OutputFormat::Exr),
+            "hdr" => Some(OutputFormat::Hdr),
+            "pfm" => Some(OutputFormat::Pfm),
+            _ => ImageFormat::from_extension(extension)
+                .filter(|format| format.can_write())
+                .map(OutputFormat::Ldr),
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<OutputFormat> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(OutputFormat::from_extension)
+    }
+}
+
+/// Linear floating-point pixels, top row first, as they come out of the renderer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<Rgb<f64>>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Rgb([0.0, 0.0, 0.0]); (width * height) as usize],
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Rgb<f64> {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, pixel: Rgb<f64>) {
+        self.pixels[(y * self.width + x) as usize] = pixel;
+    }
+
+    /// Tone mapped, sRGB encoded and quantized to 8 bits per channel for display.
+    pub fn to_rgb8(&self, transform: &OutputTransform) -> RgbImage {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let pixel = transform.apply(self.get(x, y));
+            let quantize = |c: f64| (c * 255.0).round().clamp(0.0, 255.0) as u8;
+
+            Rgb([quantize(pixel[0]), quantize(pixel[1]), quantize(pixel[2])])
+        })
+    }
+
+    fn to_f32(&self) -> Vec<Rgb<f32>> {
+        self.pixels
+            .iter()
+            .map(|pixel| Rgb([pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]))
+            .collect()
+    }
+
+    /// `transform` only applies to 8-bit formats; HDR files keep the linear values.
+    pub fn save(
+        &self,
+        path: &Path,
+        format: OutputFormat,
+        transform: &OutputTransform,
+    ) -> Result<(), ImageError> {
+        match format {
+            OutputFormat::Ldr(format) => self.to_rgb8(transform).save_with_format(path, format),
+            OutputFormat::Exr => {
+                let data = self.to_f32().iter().flat_map(|pixel| pixel.0).collect();
+                let image: ImageBuffer<Rgb<f32>, Vec<f32>> =
+                    ImageBuffer::from_raw(self.width, self.height, data).unwrap();
+
+                image.save_with_format(path, ImageFormat::OpenExr)
+            }
+            OutputFormat::Hdr => {
+                let file = BufWriter::new(File::create(path)?);
+
+                HdrEncoder::new(file).encode(
+                    &self.to_f32(),
+                    self.width as usize,
+                    self.height as usize,
+                )
+            }
+            OutputFormat::Pfm => {
+                let mut file = BufWriter::new(File::create(path)?);
+                self.write_pfm(&mut file)?;
+                file.flush()?;
+
+                Ok(())
+            }
+        }
+    }
+
+    /// PFM is a text header followed by raw floats; a negative scale means little-endian. Rows
+    /// go from the bottom of the image up.
+    fn write_pfm(&self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+
+        let pixels = self.to_f32();
+        for row in pixels.chunks(self.width as usize).rev() {
+            for pixel in row {
+                for channel in pixel.0 {
+                    out.write_all(&channel.to_le_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_from_extensions() {
+        assert_eq!(OutputFormat::from_extension("EXR"), Some(OutputFormat::Exr));
+        assert_eq!(OutputFormat::from_extension("hdr"), Some(OutputFormat::Hdr));
+        assert_eq!(OutputFormat::from_extension("pfm"), Some(OutputFormat::Pfm));
+        assert_eq!(
+            OutputFormat::from_path(Path::new("output/image.png")),
+            Some(OutputFormat::Ldr(ImageFormat::Png))
+        );
+        assert_eq!(OutputFormat::from_extension("txt"), None);
+    }
+
+    #[test]
+    fn pfm_keeps_values_above_one() {
+        let mut framebuffer = Framebuffer::new(2, 2);
+        framebuffer.set(0, 0, Rgb([4.5, 0.0, 0.0]));
+
+        let mut bytes = vec![];
+        framebuffer.write_pfm(&mut bytes).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert!(bytes.starts_with(header));
+        assert_eq!(bytes.len(), header.len() + 2 * 2 * 3 * 4);
+
+        // The top row is written last.
+        let top_left = header.len() + 2 * 3 * 4;
+        let red = f32::from_le_bytes(bytes[top_left..top_left + 4].try_into().unwrap());
+        assert_eq!(red, 4.5);
+    }
+}
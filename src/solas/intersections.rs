@@ -1,32 +1,103 @@
 use super::*;
 /// Intersections
 use cgmath::{prelude::*, Vector3};
+use std::f64::consts::PI;
+use std::sync::Arc;
 
 #[derive(Copy, Clone)]
-pub struct Hit {
+pub struct Hit<'a> {
     pub t: f64,
     pub p: Vector3<f64>,
+    /// Geometric normal, pointing out of the object regardless of which side the ray came from.
     pub normal: Vector3<f64>,
-    pub material: Material,
+    /// Surface coordinates of the hit, each in [0, 1].
+    pub u: f64,
+    pub v: f64,
+    pub material: &'a dyn Material,
 }
 
-impl Hit {
-    pub fn new(t: f64, p: Vector3<f64>, normal: Vector3<f64>, material: Material) -> Hit {
+impl<'a> Hit<'a> {
+    pub fn new(
+        t: f64,
+        p: Vector3<f64>,
+        normal: Vector3<f64>,
+        (u, v): (f64, f64),
+        material: &'a dyn Material,
+    ) -> Hit<'a> {
         Hit {
             t,
             p,
             normal,
+            u,
+            v,
             material,
         }
     }
+
+    /// The normal flipped, if need be, to point back towards where the ray came from. Opaque
+    /// surfaces like walls can be hit from either side.
+    pub fn facing_normal(&self, ray: &Ray) -> Vector3<f64> {
+        if ray.direction.dot(self.normal) > 0.0 {
+            -self.normal
+        } else {
+            self.normal
+        }
+    }
+
+    /// A basis with the facing normal along +z, for materials that work in one, and the
+    /// direction back along `ray` in it.
+    pub fn shading_frame(&self, ray: &Ray) -> (Onb, Vector3<f64>) {
+        let basis = Onb::new(self.facing_normal(ray));
+        let wo = basis.coordinates(-ray.direction.normalize());
+
+        (basis, wo)
+    }
+}
+
+/// Anything a ray can be intersected with. Scenes are shared between render threads.
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, min: f64, max: f64, sampler: &mut Sampler) -> Option<Hit<'_>>;
+    fn bounding_box(&self) -> Aabb;
+
+    /// Picks a unit direction from `origin` towards a point on the object, for sampling it as a
+    /// light, along with the direction's density per unit solid angle. Shapes that can't be
+    /// sampled return `None`.
+    fn sample_direction(
+        &self,
+        _origin: Vector3<f64>,
+        _sampler: &mut Sampler,
+    ) -> Option<(Vector3<f64>, f64)> {
+        None
+    }
+
+    /// The density with which `sample_direction` picks `direction` from `origin`, or zero if the
+    /// object isn't hit that way before `max`.
+    fn direction_pdf(
+        &self,
+        _origin: Vector3<f64>,
+        _direction: Vector3<f64>,
+        _max: f64,
+        _sampler: &mut Sampler,
+    ) -> f64 {
+        0.0
+    }
+
+    /// Adds the parts of the object that give off light, and can be sampled, to `lights`.
+    fn collect_lights(&self, _lights: &mut Vec<Arc<dyn Hittable>>) {}
 }
 
-pub fn hit(ray: &Ray, min: f64, max: f64, objects: &[Sphere]) -> Option<Hit> {
-    let mut closest_hit: Option<Hit> = None;
+pub fn hit<'a>(
+    ray: &Ray,
+    min: f64,
+    max: f64,
+    objects: &'a [Box<dyn Hittable>],
+    sampler: &mut Sampler,
+) -> Option<Hit<'a>> {
+    let mut closest_hit: Option<Hit<'a>> = None;
 
     for object in objects {
         // Check to see if we actually intersect with this object.
-        if let Some(new_hit) = object.hit(&ray, min, max) {
+        if let Some(new_hit) = object.hit(ray, min, max, sampler) {
             if closest_hit.is_none() {
                 // If we don't already have a closests hit, this is it.
                 closest_hit = Some(new_hit);
@@ -42,14 +113,66 @@ pub fn hit(ray: &Ray, min: f64, max: f64, objects: &[Sphere]) -> Option<Hit> {
     closest_hit
 }
 
+/// A heterogeneous collection of objects that together make up a scene.
+#[derive(Default)]
+pub struct HittableList {
+    pub objects: Vec<Box<dyn Hittable>>,
+}
+
+impl HittableList {
+    pub fn new() -> HittableList {
+        HittableList { objects: vec![] }
+    }
+
+    pub fn push(&mut self, object: impl Hittable + 'static) {
+        self.objects.push(Box::new(object));
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl Hittable for HittableList {
+    fn hit(&self, ray: &Ray, min: f64, max: f64, sampler: &mut Sampler) -> Option<Hit<'_>> {
+        hit(ray, min, max, &self.objects, sampler)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::empty(), |bounds, object| {
+            bounds.surrounding(&object.bounding_box())
+        })
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        for object in &self.objects {
+            object.collect_lights(lights);
+        }
+    }
+}
+
+/// Maps a point on the unit sphere to (u, v): u goes around the equator starting from -x,
+/// v from the south pole to the north pole.
+fn sphere_uv(p: Vector3<f64>) -> (f64, f64) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+
+    (phi / (2.0 * PI), theta / PI)
+}
+
+#[derive(Clone)]
 pub struct Sphere {
     pub center: Vector3<f64>,
     pub radius: f64,
-    pub material: Material,
+    pub material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Vector3<f64>, radius: f64, material: Material) -> Sphere {
+    pub fn new(center: Vector3<f64>, radius: f64, material: Arc<dyn Material>) -> Sphere {
         Sphere {
             center,
             radius,
@@ -57,25 +180,276 @@ impl Sphere {
         }
     }
 
-    pub fn hit(&self, ray: &super::Ray, min: f64, max: f64) -> Option<Hit> {
-        let oc = ray.origin - self.center;
-        let a = ray.direction.dot(ray.direction);
-        let b = oc.dot(ray.direction);
-        let c = oc.dot(oc) - self.radius.powi(2);
-        let discriminant = b * b - a * c;
-
-        if discriminant <= 0.0 {
+    /// Cosine of the half angle of the cone the sphere fills, seen from `to_center` away. There's
+    /// no cone from inside the sphere.
+    fn cone_cosine(&self, to_center: Vector3<f64>) -> Option<f64> {
+        let distance2 = to_center.magnitude2();
+        if distance2 <= self.radius * self.radius {
             return None;
         }
 
-        let temp = (-b - discriminant.sqrt()) / a;
+        Some((1.0 - self.radius * self.radius / distance2).sqrt())
+    }
+}
+
+fn cone_pdf(cos_max: f64) -> f64 {
+    1.0 / (2.0 * PI * (1.0 - cos_max))
+}
+
+fn hit_sphere<'a>(
+    center: Vector3<f64>,
+    radius: f64,
+    material: &'a dyn Material,
+    ray: &Ray,
+    min: f64,
+    max: f64,
+) -> Option<Hit<'a>> {
+    let oc = ray.origin - center;
+    let a = ray.direction.dot(ray.direction);
+    let b = oc.dot(ray.direction);
+    let c = oc.dot(oc) - radius.powi(2);
+    let discriminant = b * b - a * c;
+
+    if discriminant <= 0.0 {
+        return None;
+    }
+
+    // Try the near intersection first, then the far one for rays starting inside the sphere.
+    for temp in [
+        (-b - discriminant.sqrt()) / a,
+        (-b + discriminant.sqrt()) / a,
+    ] {
         if temp < max && temp > min {
             let point = ray.point(temp);
-            let normal = (point - self.center) / self.radius;
+            let normal = (point - center) / radius;
 
-            return Some(Hit::new(temp, point, normal, self.material));
+            return Some(Hit::new(temp, point, normal, sphere_uv(normal), material));
         }
+    }
 
-        None
+    None
+}
+
+fn sphere_bounds(center: Vector3<f64>, radius: f64) -> Aabb {
+    let extent = Vector3::new(radius, radius, radius);
+
+    Aabb::new(center - extent, center + extent)
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, ray: &Ray, min: f64, max: f64, _sampler: &mut Sampler) -> Option<Hit<'_>> {
+        hit_sphere(
+            self.center,
+            self.radius,
+            self.material.as_ref(),
+            ray,
+            min,
+            max,
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        sphere_bounds(self.center, self.radius)
+    }
+
+    /// Samples the cone of directions in which the sphere is seen from `origin`, uniformly.
+    fn sample_direction(
+        &self,
+        origin: Vector3<f64>,
+        sampler: &mut Sampler,
+    ) -> Option<(Vector3<f64>, f64)> {
+        let to_center = self.center - origin;
+        let cos_max = self.cone_cosine(to_center)?;
+
+        let z = 1.0 + sampler.next_f64() * (cos_max - 1.0);
+        let phi = 2.0 * PI * sampler.next_f64();
+        let sin = (1.0 - z * z).max(0.0).sqrt();
+        let direction =
+            Onb::new(to_center).local(Vector3::new(phi.cos() * sin, phi.sin() * sin, z));
+
+        Some((direction, cone_pdf(cos_max)))
+    }
+
+    fn direction_pdf(
+        &self,
+        origin: Vector3<f64>,
+        direction: Vector3<f64>,
+        max: f64,
+        sampler: &mut Sampler,
+    ) -> f64 {
+        let ray = Ray::new(origin, direction);
+        if self.hit(&ray, 0.001, max, sampler).is_none() {
+            return 0.0;
+        }
+
+        self.cone_cosine(self.center - origin).map_or(0.0, cone_pdf)
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        if self.material.is_emissive() {
+            lights.push(Arc::new(self.clone()));
+        }
+    }
+}
+
+/// A sphere moving in a straight line from `center0` at `time0` to `center1` at `time1`. It
+/// waits at `center0` before then and stays at `center1` after.
+pub struct MovingSphere {
+    pub center0: Vector3<f64>,
+    pub center1: Vector3<f64>,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        (center0, time0): (Vector3<f64>, f64),
+        (center1, time1): (Vector3<f64>, f64),
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> MovingSphere {
+        assert!(
+            time1 > time0,
+            "a moving sphere's time1 must be after its time0"
+        );
+
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    /// The center at `time`, held at the keyframes outside `time0..time1` so a shutter wider
+    /// than the motion can't carry the sphere out of its bounding box.
+    pub fn center(&self, time: f64) -> Vector3<f64> {
+        let t = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+
+        self.center0 + (self.center1 - self.center0) * t
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, min: f64, max: f64, _sampler: &mut Sampler) -> Option<Hit<'_>> {
+        hit_sphere(
+            self.center(ray.time),
+            self.radius,
+            self.material.as_ref(),
+            ray,
+            min,
+            max,
+        )
+    }
+
+    /// Covers the sphere between its two keyframes, which is where the camera's shutter
+    /// interval is expected to fall.
+    fn bounding_box(&self) -> Aabb {
+        sphere_bounds(self.center0, self.radius)
+            .surrounding(&sphere_bounds(self.center1, self.radius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey() -> Arc<dyn Material> {
+        make_lambertian(Vector3::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn list_reports_the_nearest_hit() {
+        let mut world = HittableList::new();
+        assert!(world.is_empty());
+        world.push(Sphere::new(Vector3::new(0.0, 0.0, -10.0), 1.0, grey()));
+        world.push(Sphere::new(Vector3::new(0.0, 0.0, -4.0), 1.0, grey()));
+        world.push(Sphere::new(Vector3::new(5.0, 0.0, -2.0), 1.0, grey()));
+        assert_eq!(world.len(), 3);
+
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0));
+        let sampler = &mut Sampler::new(DEFAULT_SEED, 0);
+        assert!((world.hit(&ray, 0.001, 10000.0, sampler).unwrap().t - 3.0).abs() < 1e-12);
+        // Both spheres in the ray's path are beyond max.
+        assert!(world.hit(&ray, 0.001, 2.5, sampler).is_none());
+        assert!(HittableList::new()
+            .hit(&ray, 0.001, 10000.0, sampler)
+            .is_none());
+    }
+
+    #[test]
+    fn list_bounds_all_of_its_objects() {
+        let mut world = HittableList::new();
+        world.push(Sphere::new(Vector3::new(0.0, 0.0, -10.0), 1.0, grey()));
+        world.push(Sphere::new(Vector3::new(5.0, 0.0, -2.0), 0.5, grey()));
+
+        let bounds = world.bounding_box();
+        assert_eq!(bounds.min, Vector3::new(-1.0, -1.0, -11.0));
+        assert_eq!(bounds.max, Vector3::new(5.5, 1.0, -1.5));
+    }
+
+    #[test]
+    fn ray_from_inside_a_sphere_hits_its_far_side() {
+        let sphere = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 2.0, grey());
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+
+        let hit = sphere
+            .hit(&ray, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+            .unwrap();
+        assert!((hit.t - 3.0).abs() < 1e-12);
+        assert_eq!(hit.normal, Vector3::new(0.0, 0.0, -1.0));
+        // The outward normal faces away from the ray, so shading flips it back inside.
+        assert_eq!(hit.facing_normal(&ray), Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn moving_sphere_follows_ray_time() {
+        let sphere = MovingSphere::new(
+            (Vector3::new(0.0, 0.0, 0.0), 0.0),
+            (Vector3::new(0.0, 2.0, 0.0), 1.0),
+            0.5,
+            make_lambertian(Vector3::new(0.5, 0.5, 0.5)),
+        );
+        let origin = Vector3::new(0.0, 1.0, 5.0);
+        let direction = Vector3::new(0.0, 0.0, -1.0);
+
+        let hit = sphere.hit(
+            &Ray::with_time(origin, direction, 0.5),
+            0.001,
+            10000.0,
+            &mut Sampler::new(DEFAULT_SEED, 0),
+        );
+        assert!((hit.unwrap().t - 4.5).abs() < 1e-12);
+        assert!(sphere
+            .hit(
+                &Ray::with_time(origin, direction, 0.0),
+                0.001,
+                10000.0,
+                &mut Sampler::new(DEFAULT_SEED, 0)
+            )
+            .is_none());
+
+        let bounds = sphere.bounding_box();
+        assert_eq!(bounds.min, Vector3::new(-0.5, -0.5, -0.5));
+        assert_eq!(bounds.max, Vector3::new(0.5, 2.5, 0.5));
+
+        // Outside the keyframes the sphere stays put rather than leaving its bounds.
+        assert_eq!(sphere.center(-1.0), Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(sphere.center(3.0), Vector3::new(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "time1 must be after its time0")]
+    fn moving_sphere_needs_an_interval() {
+        MovingSphere::new(
+            (Vector3::new(0.0, 0.0, 0.0), 1.0),
+            (Vector3::new(0.0, 2.0, 0.0), 1.0),
+            0.5,
+            grey(),
+        );
     }
 }
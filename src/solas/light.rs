@@ -0,0 +1,179 @@
+// Light sampling
+
+use super::*;
+use cgmath::Vector3;
+use std::sync::Arc;
+
+/// The scene's emitters that can be sampled directly, so surfaces are lit by aiming rays at the
+/// lights rather than only by bounces that happen to find them. Only spheres and quads (and so
+/// boxes) placed straight in the world can be sampled. Instanced ones, triangles, moving spheres
+/// and volumes that emit still light the scene, but only through bounces.
+#[derive(Default)]
+pub struct LightList {
+    lights: Vec<Arc<dyn Hittable>>,
+}
+
+impl LightList {
+    pub fn new(world: &dyn Hittable) -> LightList {
+        let mut lights = vec![];
+        world.collect_lights(&mut lights);
+
+        LightList { lights }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// Picks a light at random and a direction from `origin` towards it. Returns the light, the
+    /// direction and its density, counting the chance of having picked that light.
+    pub fn sample(
+        &self,
+        origin: Vector3<f64>,
+        sampler: &mut Sampler,
+    ) -> Option<(&dyn Hittable, Vector3<f64>, f64)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let index = (sampler.next_u32() as usize) % self.lights.len();
+        let light = self.lights[index].as_ref();
+        let (direction, pdf) = light.sample_direction(origin, sampler)?;
+
+        Some((light, direction, pdf / self.lights.len() as f64))
+    }
+
+    /// The density with which `sample` would have picked `direction` from `origin`, counting
+    /// only the light it reaches before `max`: the one actually hit.
+    pub fn pdf(
+        &self,
+        origin: Vector3<f64>,
+        direction: Vector3<f64>,
+        max: f64,
+        sampler: &mut Sampler,
+    ) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+
+        let total: f64 = self
+            .lights
+            .iter()
+            .map(|light| light.direction_pdf(origin, direction, max, sampler))
+            .sum();
+
+        total / self.lights.len() as f64
+    }
+}
+
+/// Weight for a sample taken with density `pdf`, when `other` is the density the other strategy
+/// would have taken it with: Veach's power heuristic, with an exponent of two.
+pub fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b == 0.0 {
+        return 0.0;
+    }
+
+    a / (a + b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::prelude::*;
+
+    fn light() -> Arc<dyn Material> {
+        make_diffuse_light(Vector3::new(4.0, 4.0, 4.0))
+    }
+
+    fn white() -> Arc<dyn Material> {
+        make_lambertian(Vector3::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn only_emitters_are_collected() {
+        let mut world = HittableList::new();
+        world.push(Sphere::new(Vector3::new(0.0, 5.0, 0.0), 1.0, light()));
+        world.push(Sphere::new(Vector3::new(0.0, -100.0, 0.0), 99.0, white()));
+        world.push(Cuboid::new(
+            Vector3::new(-1.0, -1.0, -1.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            light(),
+        ));
+
+        let lights = LightList::new(&Bvh::new(world));
+        assert_eq!(lights.len(), 1 + 6);
+    }
+
+    /// The density reported for a sampled direction matches what `pdf` says about it, and the
+    /// densities integrate to one over the light's solid angle.
+    fn check_sampling(shape: impl Hittable + 'static, origin: Vector3<f64>) {
+        let mut world = HittableList::new();
+        world.push(shape);
+        let lights = LightList::new(&world);
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+
+        for _ in 0..100 {
+            let (_, direction, pdf) = lights.sample(origin, &mut sampler).unwrap();
+            let expected = lights.pdf(origin, direction, f64::INFINITY, &mut sampler);
+            assert!(
+                (pdf - expected).abs() < 1e-6 * expected,
+                "{} {}",
+                pdf,
+                expected
+            );
+        }
+
+        // Monte Carlo estimate of the integral, over uniformly sampled directions.
+        let count = 200_000;
+        let mut total = 0.0;
+        for _ in 0..count {
+            let direction = random_in_unit_sphere(&mut sampler).normalize();
+            total += lights.pdf(origin, direction, f64::INFINITY, &mut sampler);
+        }
+        let integral = total * 4.0 * std::f64::consts::PI / count as f64;
+        assert!((integral - 1.0).abs() < 0.05, "{}", integral);
+    }
+
+    #[test]
+    fn sphere_sampling() {
+        check_sampling(
+            Sphere::new(Vector3::new(0.0, 3.0, 0.0), 1.0, light()),
+            Vector3::new(0.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn quad_sampling() {
+        check_sampling(
+            Quad::xz_rect(-1.0, 2.0, -1.0, 1.0, 2.0, light()),
+            Vector3::new(0.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn occluded_lights_have_no_density() {
+        let mut world = HittableList::new();
+        world.push(Sphere::new(Vector3::new(0.0, 3.0, 0.0), 1.0, light()));
+        let lights = LightList::new(&world);
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        assert!(lights.pdf(Vector3::new(0.0, 0.0, 0.0), up, f64::INFINITY, &mut sampler) > 0.0);
+        assert_eq!(
+            lights.pdf(Vector3::new(0.0, 0.0, 0.0), up, 1.0, &mut sampler),
+            0.0
+        );
+    }
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        let (a, b) = (0.3, 1.7);
+        assert!((power_heuristic(a, b) + power_heuristic(b, a) - 1.0).abs() < 1e-12);
+        assert_eq!(power_heuristic(1.0, 0.0), 1.0);
+    }
+}
@@ -2,82 +2,159 @@
 
 use super::*;
 use cgmath::{prelude::*, Vector3};
-use random_number::random;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// A direction light is scattered in, with what the renderer needs to weight it: the light
+/// coming back along `ray` is multiplied by `f * cosine / pdf`.
+pub struct Scatter {
+    pub ray: Ray,
+    /// The scattering function (the BSDF, or the phase function in a medium) for light coming
+    /// in along `ray` and leaving back along the incoming ray.
+    pub f: Vector3<f64>,
+    /// The cosine between `ray` and the surface normal; one in a medium.
+    pub cosine: f64,
+    /// The density with which the direction was picked, per unit solid angle.
+    pub pdf: f64,
+    /// Picked as the only possible direction, like a mirror's, so that light sampling can't
+    /// help. `f` is then the attenuation, and `cosine` and `pdf` are one.
+    pub specular: bool,
+}
 
-#[derive(Copy, Clone)]
-// TODO: Super lame version of Material, to be replaced with a Material Trait once I know how to do that.
-pub struct Material {
-    lambertian: Option<LambertianMaterial>,
-    metal: Option<MetalMaterial>,
-    dialectric: Option<DialectricMaterial>,
+impl Scatter {
+    /// A specular scatter, which attenuates the light by `attenuation`.
+    pub fn specular(ray: Ray, attenuation: Vector3<f64>) -> Scatter {
+        Scatter {
+            ray,
+            f: attenuation,
+            cosine: 1.0,
+            pdf: 1.0,
+            specular: true,
+        }
+    }
+
+    /// What the light coming back along `ray` gets multiplied by.
+    pub fn weight(&self) -> Vector3<f64> {
+        self.f * (self.cosine / self.pdf)
+    }
 }
 
-// Temporary helper functions until a better Material API can be built.
+/// How light interacts with a surface. Implement this to add new kinds of material; objects
+/// share materials through an `Arc`, and each `Hit` borrows the material it landed on.
+pub trait Material: Send + Sync {
+    /// Picks a direction to scatter `ray` in, or returns `None` if the ray was absorbed.
+    fn scatter(&self, ray: &Ray, hit: &Hit, sampler: &mut Sampler) -> Option<Scatter>;
+
+    /// The scattering into a given `direction`, as if `scatter` had picked it, so lights can be
+    /// sampled directly. Only materials with a non-specular scattering function can do this;
+    /// the rest return `None` and only see lights their scattered rays happen to hit.
+    fn evaluate(&self, _ray: &Ray, _hit: &Hit, _direction: Vector3<f64>) -> Option<Scatter> {
+        None
+    }
+
+    /// Light given off by the surface itself. Most materials don't glow.
+    fn emitted(&self, _hit: &Hit) -> Vector3<f64> {
+        Vector3::new(0.0, 0.0, 0.0)
+    }
 
-pub fn make_lambertian(albedo: Vector3<f64>) -> Material {
-    Material::new(Some(LambertianMaterial::new(albedo)), None, None)
+    /// Whether `emitted` gives off anything, so objects made of it can be sampled as lights.
+    fn is_emissive(&self) -> bool {
+        false
+    }
 }
 
-pub fn make_metal(albedo: Vector3<f64>, fuzz: f64) -> Material {
-    Material::new(None, Some(MetalMaterial::new(albedo, fuzz)), None)
+// Convenience constructors for the built-in materials.
+
+pub fn make_lambertian(albedo: Vector3<f64>) -> Arc<dyn Material> {
+    make_textured_lambertian(Arc::new(SolidColor::new(albedo)))
 }
 
-pub fn make_dialectric(refractive_index: f64) -> Material {
-    Material::new(None, None, Some(DialectricMaterial::new(refractive_index)))
+pub fn make_textured_lambertian(albedo: Arc<dyn Texture>) -> Arc<dyn Material> {
+    Arc::new(LambertianMaterial::new(albedo))
 }
 
-impl Material {
-    pub fn new(
-        lambertian: Option<LambertianMaterial>,
-        metal: Option<MetalMaterial>,
-        dialectric: Option<DialectricMaterial>,
-    ) -> Self {
-        Material {
-            lambertian,
-            metal,
-            dialectric,
-        }
-    }
+pub fn make_metal(albedo: Vector3<f64>, fuzz: f64) -> Arc<dyn Material> {
+    make_textured_metal(Arc::new(SolidColor::new(albedo)), fuzz)
+}
 
-    pub fn scatter(&self, ray: &Ray, hit: Hit) -> Option<(Vector3<f64>, Ray)> {
-        if let Some(lambertian) = self.lambertian {
-            return lambertian.scatter(ray, hit);
-        }
+pub fn make_textured_metal(albedo: Arc<dyn Texture>, fuzz: f64) -> Arc<dyn Material> {
+    Arc::new(MetalMaterial::new(albedo, fuzz))
+}
 
-        if let Some(metal) = self.metal {
-            return metal.scatter(ray, hit);
-        }
+pub fn make_dialectric(refractive_index: f64) -> Arc<dyn Material> {
+    Arc::new(DialectricMaterial::new(refractive_index))
+}
 
-        if let Some(dialectric) = self.dialectric {
-            return dialectric.scatter(ray, hit);
-        }
+/// A rough metal; see `ConductorMaterial`.
+pub fn make_conductor(metal: Metal, roughness: f64) -> Arc<dyn Material> {
+    Arc::new(ConductorMaterial::from_metal(metal, roughness))
+}
 
-        None
-    }
+/// Glass with a frosted surface; a `roughness` of zero makes it clear.
+pub fn make_rough_dielectric(refractive_index: f64, roughness: f64) -> Arc<dyn Material> {
+    Arc::new(RoughDielectricMaterial::new(refractive_index, roughness))
 }
 
-#[derive(Copy, Clone)]
+/// A principled material with a solid base color, e.g.
+/// `make_principled(color, Principled { metallic: 1.0, ..Default::default() })`.
+pub fn make_principled(base_color: Vector3<f64>, parameters: Principled) -> Arc<dyn Material> {
+    make_textured_principled(Arc::new(SolidColor::new(base_color)), parameters)
+}
+
+pub fn make_textured_principled(
+    base_color: Arc<dyn Texture>,
+    parameters: Principled,
+) -> Arc<dyn Material> {
+    Arc::new(PrincipledMaterial::new(base_color, parameters))
+}
+
+pub fn make_diffuse_light(emit: Vector3<f64>) -> Arc<dyn Material> {
+    Arc::new(DiffuseLight::new(emit))
+}
+
+pub fn make_isotropic(albedo: Vector3<f64>) -> Arc<dyn Material> {
+    Arc::new(IsotropicMaterial::new(Arc::new(SolidColor::new(albedo))))
+}
+
+#[derive(Clone)]
 pub struct LambertianMaterial {
-    albedo: Vector3<f64>,
+    albedo: Arc<dyn Texture>,
 }
 
 impl LambertianMaterial {
-    pub fn new(albedo: Vector3<f64>) -> Self {
-        LambertianMaterial { albedo: albedo }
+    pub fn new(albedo: Arc<dyn Texture>) -> Self {
+        LambertianMaterial { albedo }
+    }
+}
+
+impl Material for LambertianMaterial {
+    fn scatter(&self, ray: &Ray, hit: &Hit, sampler: &mut Sampler) -> Option<Scatter> {
+        let basis = Onb::new(hit.facing_normal(ray));
+        let direction = basis.local(random_cosine_direction(sampler));
+
+        self.evaluate(ray, hit, direction)
     }
 
-    pub fn scatter(&self, _ray: &Ray, hit: Hit) -> Option<(Vector3<f64>, Ray)> {
-        let target = hit.p + hit.normal + random_in_unit_sphere();
-        let scattered = Ray::new(hit.p, target - hit.p);
-        let attenuation = self.albedo;
+    fn evaluate(&self, ray: &Ray, hit: &Hit, direction: Vector3<f64>) -> Option<Scatter> {
+        let direction = direction.normalize();
+        let cosine = hit.facing_normal(ray).dot(direction);
+        if cosine <= 0.0 {
+            return None;
+        }
 
-        Some((attenuation, scattered))
+        Some(Scatter {
+            ray: Ray::with_time(hit.p, direction, ray.time),
+            f: self.albedo.value(hit.u, hit.v, hit.p) / PI,
+            cosine,
+            pdf: cosine / PI,
+            specular: false,
+        })
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct MetalMaterial {
-    albedo: Vector3<f64>,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
 }
 
@@ -91,9 +168,9 @@ fn refract(v: Vector3<f64>, n: Vector3<f64>, ni_over_nt: f64) -> Option<Vector3<
     let discriminant = 1.0 - ni_over_nt * ni_over_nt * (1.0 - dt * dt);
 
     if discriminant > 0.0 {
-        return Some((uv - n * dt) * ni_over_nt - n * discriminant.sqrt());
+        Some((uv - n * dt) * ni_over_nt - n * discriminant.sqrt())
     } else {
-        return None;
+        None
     }
 }
 
@@ -105,22 +182,26 @@ fn schlick(cosine: f64, refraction_index: f64) -> f64 {
 }
 
 impl MetalMaterial {
-    pub fn new(albedo: Vector3<f64>, fuzz: f64) -> Self {
+    pub fn new(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self { albedo, fuzz }
     }
+}
 
-    pub fn scatter(&self, ray: &Ray, hit: Hit) -> Option<(Vector3<f64>, Ray)> {
-        let reflected = reflect(ray.direction.normalize(), hit.normal);
+impl Material for MetalMaterial {
+    /// Fuzzy reflections count as specular too: the fuzz has no density to weight by.
+    fn scatter(&self, ray: &Ray, hit: &Hit, sampler: &mut Sampler) -> Option<Scatter> {
+        let normal = hit.facing_normal(ray);
+        let reflected = reflect(ray.direction.normalize(), normal);
 
-        let scattered_direction = reflected + (random_in_unit_sphere() * self.fuzz);
-        let scattered = Ray::new(hit.p, scattered_direction);
-        let attenuation = self.albedo;
+        let scattered_direction = reflected + (random_in_unit_sphere(sampler) * self.fuzz);
+        let scattered = Ray::with_time(hit.p, scattered_direction, ray.time);
+        let attenuation = self.albedo.value(hit.u, hit.v, hit.p);
 
-        if scattered.direction.dot(hit.normal) <= 0.0 {
+        if scattered.direction.dot(normal) <= 0.0 {
             return None;
         }
 
-        Some((attenuation, scattered))
+        Some(Scatter::specular(scattered, attenuation))
     }
 }
 
@@ -133,8 +214,10 @@ impl DialectricMaterial {
     pub fn new(refractive_index: f64) -> Self {
         DialectricMaterial { refractive_index }
     }
+}
 
-    pub fn scatter(&self, ray: &Ray, hit: Hit) -> Option<(Vector3<f64>, Ray)> {
+impl Material for DialectricMaterial {
+    fn scatter(&self, ray: &Ray, hit: &Hit, sampler: &mut Sampler) -> Option<Scatter> {
         let reflected = reflect(ray.direction, hit.normal);
         let attenuation = Vector3::new(1.0, 1.0, 1.0);
 
@@ -152,20 +235,217 @@ impl DialectricMaterial {
             cosine = -ray.direction.dot(hit.normal) / ray.direction.magnitude();
         }
 
-        if let Some(refracted) = refract(ray.direction, outward_normal, ni_over_nt) {
-            let reflect_prob = schlick(cosine, self.refractive_index);
-            let refraction_chance: f64 = random!();
-            if refraction_chance < reflect_prob {
-                return Some((attenuation, Ray::new(hit.p, reflected)));
-            } else {
-                return Some((attenuation, Ray::new(hit.p, refracted)));
+        let direction = match refract(ray.direction, outward_normal, ni_over_nt) {
+            Some(refracted) if sampler.next_f64() >= schlick(cosine, self.refractive_index) => {
+                refracted
             }
+            _ => reflected,
+        };
+
+        Some(Scatter::specular(
+            Ray::with_time(hit.p, direction, ray.time),
+            attenuation,
+        ))
+    }
+}
+
+/// A metal whose surface is made of GGX microfacets, each reflecting by the Fresnel equations
+/// for the metal's complex refractive index `eta + ik`. Unlike the fuzz of `MetalMaterial`,
+/// roughness spreads the reflection out without making up or losing light, besides what the
+/// facets shadow from each other.
+#[derive(Copy, Clone)]
+pub struct ConductorMaterial {
+    eta: Vector3<f64>,
+    k: Vector3<f64>,
+    distribution: Ggx,
+}
+
+impl ConductorMaterial {
+    pub fn new(eta: Vector3<f64>, k: Vector3<f64>, roughness: f64) -> Self {
+        ConductorMaterial {
+            eta,
+            k,
+            distribution: Ggx::new(roughness),
+        }
+    }
+
+    pub fn from_metal(metal: Metal, roughness: f64) -> Self {
+        ConductorMaterial::new(metal.eta(), metal.k(), roughness)
+    }
+}
+
+impl Material for ConductorMaterial {
+    fn scatter(&self, ray: &Ray, hit: &Hit, sampler: &mut Sampler) -> Option<Scatter> {
+        let (basis, wo) = hit.shading_frame(ray);
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.is_smooth() {
+            let direction = basis.local(Vector3::new(-wo.x, -wo.y, wo.z));
+            let reflectance = fresnel_conductor(wo.z, self.eta, self.k);
+
+            return Some(Scatter::specular(
+                Ray::with_time(hit.p, direction, ray.time),
+                reflectance,
+            ));
+        }
+
+        let m = self.distribution.sample_visible(wo, sampler);
+        let wi = reflect_about(wo, m);
+
+        self.evaluate(ray, hit, basis.local(wi))
+    }
+
+    fn evaluate(&self, ray: &Ray, hit: &Hit, direction: Vector3<f64>) -> Option<Scatter> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+
+        let (basis, wo) = hit.shading_frame(ray);
+        let wi = basis.coordinates(direction.normalize());
+        let (m, brdf, pdf) = self.distribution.reflection(wo, wi)?;
+        let reflectance = fresnel_conductor(wi.dot(m), self.eta, self.k);
+
+        Some(Scatter {
+            ray: Ray::with_time(hit.p, basis.local(wi), ray.time),
+            f: reflectance * brdf,
+            cosine: wi.z,
+            pdf,
+            specular: false,
+        })
+    }
+}
+
+/// Glass with a surface of GGX microfacets, each reflecting or refracting like
+/// `DialectricMaterial` does but with the exact Fresnel equations. Like `DialectricMaterial`,
+/// radiance isn't rescaled as it crosses into the denser medium; for closed objects that cancels
+/// out on the way back.
+#[derive(Copy, Clone)]
+pub struct RoughDielectricMaterial {
+    refractive_index: f64,
+    distribution: Ggx,
+}
+
+impl RoughDielectricMaterial {
+    pub fn new(refractive_index: f64, roughness: f64) -> Self {
+        RoughDielectricMaterial {
+            refractive_index,
+            distribution: Ggx::new(roughness),
+        }
+    }
+
+    /// How many times denser the medium on the far side of the surface is than the ray's.
+    fn relative_index(&self, ray: &Ray, hit: &Hit) -> f64 {
+        if ray.direction.dot(hit.normal) > 0.0 {
+            1.0 / self.refractive_index
         } else {
-            return Some((attenuation, Ray::new(hit.p, reflected)));
+            self.refractive_index
         }
     }
 }
 
+impl Material for RoughDielectricMaterial {
+    fn scatter(&self, ray: &Ray, hit: &Hit, sampler: &mut Sampler) -> Option<Scatter> {
+        let (basis, wo) = hit.shading_frame(ray);
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let eta = self.relative_index(ray, hit);
+        let wi = self.distribution.sample_dielectric(wo, eta, sampler)?;
+
+        if self.distribution.is_smooth() {
+            let attenuation = Vector3::new(1.0, 1.0, 1.0);
+            return Some(Scatter::specular(
+                Ray::with_time(hit.p, basis.local(wi), ray.time),
+                attenuation,
+            ));
+        }
+
+        self.evaluate(ray, hit, basis.local(wi))
+    }
+
+    fn evaluate(&self, ray: &Ray, hit: &Hit, direction: Vector3<f64>) -> Option<Scatter> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+
+        let (basis, wo) = hit.shading_frame(ray);
+        let wi = basis.coordinates(direction.normalize());
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return None;
+        }
+        let eta = self.relative_index(ray, hit);
+        let (f, pdf) = self.distribution.dielectric(wo, wi, eta)?;
+
+        Some(Scatter {
+            ray: Ray::with_time(hit.p, basis.local(wi), ray.time),
+            f: Vector3::new(f, f, f),
+            cosine: wi.z.abs(),
+            pdf,
+            specular: false,
+        })
+    }
+}
+
+/// A light source: emits `emit` from every point and doesn't reflect anything.
+#[derive(Copy, Clone)]
+pub struct DiffuseLight {
+    emit: Vector3<f64>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Vector3<f64>) -> Self {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &Hit, _sampler: &mut Sampler) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _hit: &Hit) -> Vector3<f64> {
+        self.emit
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.emit != Vector3::new(0.0, 0.0, 0.0)
+    }
+}
+
+/// Phase function for participating media: light is scattered equally in every direction.
+#[derive(Clone)]
+pub struct IsotropicMaterial {
+    albedo: Arc<dyn Texture>,
+}
+
+impl IsotropicMaterial {
+    pub fn new(albedo: Arc<dyn Texture>) -> Self {
+        IsotropicMaterial { albedo }
+    }
+}
+
+impl Material for IsotropicMaterial {
+    fn scatter(&self, ray: &Ray, hit: &Hit, sampler: &mut Sampler) -> Option<Scatter> {
+        let direction = random_in_unit_sphere(sampler);
+
+        self.evaluate(ray, hit, direction)
+    }
+
+    fn evaluate(&self, ray: &Ray, hit: &Hit, direction: Vector3<f64>) -> Option<Scatter> {
+        let uniform = 1.0 / (4.0 * PI);
+
+        Some(Scatter {
+            ray: Ray::with_time(hit.p, direction.normalize(), ray.time),
+            f: self.albedo.value(hit.u, hit.v, hit.p) * uniform,
+            cosine: 1.0,
+            pdf: uniform,
+            specular: false,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,7 +462,7 @@ mod tests {
 
     struct DialectricTests {
         ray: Ray,
-        hit: Hit,
+        material: DialectricMaterial,
     }
 
     impl DialectricTests {
@@ -192,14 +472,19 @@ mod tests {
                     Vector3::new(-0.240156129, -0.61830759, -5.85693741),
                     Vector3::new(-0.0104245991, 0.0229948163, 0.13193655),
                 ),
-                hit: Hit::new(
-                    34.9683533,
-                    Vector3::new(-0.604687213, 0.185783267, -1.24333334),
-                    Vector3::new(0.790625572, 0.371566534, -0.486666679),
-                    make_dialectric(1.5),
-                ),
+                material: DialectricMaterial::new(1.5),
             }
         }
+
+        fn hit(&self) -> Hit<'_> {
+            Hit::new(
+                34.9683533,
+                Vector3::new(-0.604687213, 0.185783267, -1.24333334),
+                Vector3::new(0.790625572, 0.371566534, -0.486666679),
+                (0.0, 0.0),
+                &self.material,
+            )
+        }
     }
 
     #[test]
@@ -226,14 +511,19 @@ mod tests {
         let expected_scatter_origin = Vector3::new(-0.604687213, 0.185783267, -1.24333334);
         let expected_scatter_dir = Vector3::new(-0.441409051, -0.0690121651, 0.894647479);
 
-        let (_, scatter) = setup.hit.material.scatter(&setup.ray, setup.hit).unwrap();
-        assert!(nearly_equal(scatter.origin, expected_scatter_origin));
-        assert!(nearly_equal(scatter.direction, expected_scatter_dir));
+        let hit = setup.hit();
+        let scatter = hit
+            .material
+            .scatter(&setup.ray, &hit, &mut Sampler::new(DEFAULT_SEED, 0))
+            .unwrap();
+        assert!(scatter.specular);
+        assert!(nearly_equal(scatter.ray.origin, expected_scatter_origin));
+        assert!(nearly_equal(scatter.ray.direction, expected_scatter_dir));
     }
 
     struct MetalTests {
         ray: Ray,
-        hit: Hit,
+        material: MetalMaterial,
     }
 
     impl MetalTests {
@@ -243,15 +533,22 @@ mod tests {
                     Vector3::new(1.49196982, -0.697316408, -7.09905147),
                     Vector3::new(-0.00825381278, 0.0296297669, 0.183610916),
                 ),
-
-                hit: Hit::new(
-                    31.1511402,
-                    Vector3::new(1.2348541, 0.225684643, -1.37936211),
-                    Vector3::new(0.469708204, 0.451369286, -0.758724212),
-                    make_metal(Vector3::new(0.0, 0.0, 0.0), 0.0),
+                material: MetalMaterial::new(
+                    Arc::new(SolidColor::new(Vector3::new(0.0, 0.0, 0.0))),
+                    0.0,
                 ),
             }
         }
+
+        fn hit(&self) -> Hit<'_> {
+            Hit::new(
+                31.1511402,
+                Vector3::new(1.2348541, 0.225684643, -1.37936211),
+                Vector3::new(0.469708204, 0.451369286, -0.758724212),
+                (0.0, 0.0),
+                &self.material,
+            )
+        }
     }
 
     #[test]
@@ -259,7 +556,7 @@ mod tests {
         let setup = MetalTests::new();
         let expected_reflection = Vector3::new(0.610705376, 0.788620412, -0.0718354583);
 
-        let reflected = super::reflect(setup.ray.direction.normalize(), setup.hit.normal);
+        let reflected = super::reflect(setup.ray.direction.normalize(), setup.hit().normal);
         assert!(nearly_equal(reflected, expected_reflection));
     }
 
@@ -269,9 +566,100 @@ mod tests {
         let expected_scatter_point = Vector3::new(1.2348541, 0.225684643, -1.37936211);
         let expected_scatter_dir = Vector3::new(0.610705376, 0.788620412, -0.0718354583);
 
-        let (_, scattered) = setup.hit.material.scatter(&setup.ray, setup.hit).unwrap();
+        let hit = setup.hit();
+        let scattered = hit
+            .material
+            .scatter(&setup.ray, &hit, &mut Sampler::new(DEFAULT_SEED, 0))
+            .unwrap()
+            .ray;
 
         assert!(nearly_equal(scattered.origin, expected_scatter_point));
         assert!(nearly_equal(scattered.direction, expected_scatter_dir));
     }
+
+    /// Scatters a ray hitting the top of a surface from an angle, checking that `evaluate`
+    /// agrees with what `scatter` returned. Returns the mean weight, which can't be more than
+    /// one for a surface that doesn't glow.
+    fn mean_weight(material: &dyn Material, from_inside: bool) -> f64 {
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let direction = Vector3::new(0.6, if from_inside { 0.8 } else { -0.8 }, 0.0);
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0) - direction, direction);
+        let hit = Hit::new(
+            1.0,
+            Vector3::new(0.0, 0.0, 0.0),
+            normal,
+            (0.0, 0.0),
+            material,
+        );
+
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+        let count = 100_000;
+        let mut total = 0.0;
+        for _ in 0..count {
+            let Some(scatter) = material.scatter(&ray, &hit, &mut sampler) else {
+                continue;
+            };
+            let evaluated = material
+                .evaluate(&ray, &hit, scatter.ray.direction)
+                .unwrap();
+            assert!((evaluated.pdf - scatter.pdf).abs() < 1e-6 * scatter.pdf);
+            assert!(nearly_equal(evaluated.f, scatter.f));
+
+            let weight = scatter.weight();
+            total += (weight.x + weight.y + weight.z) / 3.0;
+        }
+
+        total / count as f64
+    }
+
+    #[test]
+    fn rough_conductor_scatters_consistently() {
+        let aluminium = ConductorMaterial::from_metal(Metal::Aluminium, 0.4);
+        let weight = mean_weight(&aluminium, false);
+        assert!(weight > 0.8 && weight < 1.0, "{}", weight);
+
+        // Without any absorption, only shadowing between the facets loses light.
+        let mirror = ConductorMaterial::new(
+            Vector3::new(1.0, 1.0, 1.0),
+            Vector3::new(1e6, 1e6, 1e6),
+            0.4,
+        );
+        let weight = mean_weight(&mirror, false);
+        assert!(weight > 0.9 && weight <= 1.0, "{}", weight);
+    }
+
+    #[test]
+    fn rough_dielectric_scatters_consistently() {
+        let glass = RoughDielectricMaterial::new(1.5, 0.4);
+
+        for from_inside in [false, true] {
+            let weight = mean_weight(&glass, from_inside);
+            assert!(weight > 0.9 && weight <= 1.0, "{} {}", from_inside, weight);
+        }
+    }
+
+    #[test]
+    fn smooth_microfacet_materials_are_specular() {
+        let glass = RoughDielectricMaterial::new(1.5, 0.0);
+        let ray = Ray::new(Vector3::new(-0.6, 0.8, 0.0), Vector3::new(0.6, -0.8, 0.0));
+        let hit = Hit::new(
+            1.0,
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            (0.0, 0.0),
+            &glass,
+        );
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+
+        let scatter = glass.scatter(&ray, &hit, &mut sampler).unwrap();
+        assert!(scatter.specular);
+
+        let gold = ConductorMaterial::from_metal(Metal::Gold, 0.0);
+        let scatter = gold.scatter(&ray, &hit, &mut sampler).unwrap();
+        assert!(scatter.specular);
+        assert!(nearly_equal(
+            scatter.ray.direction,
+            Vector3::new(0.6, 0.8, 0.0)
+        ));
+    }
 }
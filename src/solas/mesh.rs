@@ -0,0 +1,222 @@
+// Triangles and triangle meshes
+
+use super::*;
+use cgmath::{prelude::*, Vector3};
+use std::sync::Arc;
+
+/// One triangle of a mesh, as indices into the mesh's vertex data.
+#[derive(Copy, Clone, Debug)]
+pub struct Face {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    /// Index into the mesh's materials.
+    pub material: usize,
+}
+
+/// Indexed triangle storage: vertices are shared between the faces that use them.
+#[derive(Default)]
+pub struct TriangleMesh {
+    pub positions: Vec<Vector3<f64>>,
+    pub normals: Vec<Vector3<f64>>,
+    pub uvs: Vec<(f64, f64)>,
+    pub materials: Vec<Arc<dyn Material>>,
+    pub faces: Vec<Face>,
+}
+
+impl TriangleMesh {
+    /// Splits the mesh into its triangles, so each one can go into the scene's BVH on its own.
+    pub fn into_triangles(self) -> Vec<Triangle> {
+        let mesh = Arc::new(self);
+
+        (0..mesh.faces.len())
+            .map(|face| Triangle {
+                mesh: mesh.clone(),
+                face,
+            })
+            .collect()
+    }
+}
+
+/// A single triangle, sharing its vertices with the rest of its mesh.
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+}
+
+impl Triangle {
+    /// A stand-alone triangle. Counter-clockwise when seen from the front.
+    pub fn new(
+        a: Vector3<f64>,
+        b: Vector3<f64>,
+        c: Vector3<f64>,
+        material: Arc<dyn Material>,
+    ) -> Triangle {
+        let mesh = TriangleMesh {
+            positions: vec![a, b, c],
+            materials: vec![material],
+            faces: vec![Face {
+                positions: [0, 1, 2],
+                normals: None,
+                uvs: None,
+                material: 0,
+            }],
+            ..Default::default()
+        };
+
+        mesh.into_triangles().pop().unwrap()
+    }
+
+    fn face(&self) -> &Face {
+        &self.mesh.faces[self.face]
+    }
+
+    fn vertices(&self) -> [Vector3<f64>; 3] {
+        self.face()
+            .positions
+            .map(|index| self.mesh.positions[index])
+    }
+}
+
+impl Hittable for Triangle {
+    // Möller–Trumbore
+    fn hit(&self, ray: &Ray, min: f64, max: f64, _sampler: &mut Sampler) -> Option<Hit<'_>> {
+        let [a, b, c] = self.vertices();
+        let edge1 = b - a;
+        let edge2 = c - a;
+
+        let p = ray.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+
+        // Parallel to the triangle.
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+
+        let inverse = 1.0 / determinant;
+        let s = ray.origin - a;
+        let beta = s.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let gamma = ray.direction.dot(q) * inverse;
+        if gamma < 0.0 || beta + gamma > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(q) * inverse;
+        if t <= min || t >= max {
+            return None;
+        }
+
+        let alpha = 1.0 - beta - gamma;
+        let face = self.face();
+
+        let normal = match face.normals {
+            Some([na, nb, nc]) => {
+                let normals = &self.mesh.normals;
+                (normals[na] * alpha + normals[nb] * beta + normals[nc] * gamma).normalize()
+            }
+            None => edge1.cross(edge2).normalize(),
+        };
+
+        let uv = match face.uvs {
+            Some([ta, tb, tc]) => {
+                let uvs = &self.mesh.uvs;
+                (
+                    uvs[ta].0 * alpha + uvs[tb].0 * beta + uvs[tc].0 * gamma,
+                    uvs[ta].1 * alpha + uvs[tb].1 * beta + uvs[tc].1 * gamma,
+                )
+            }
+            None => (beta, gamma),
+        };
+
+        Some(Hit::new(
+            t,
+            ray.point(t),
+            normal,
+            uv,
+            self.mesh.materials[face.material].as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.vertices()
+            .iter()
+            .fold(Aabb::empty(), |bounds, vertex| {
+                bounds.surrounding(&Aabb::new(*vertex, *vertex))
+            })
+            .pad(0.0001)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white() -> Arc<dyn Material> {
+        make_lambertian(Vector3::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn triangle_hit_reports_barycentrics() {
+        let triangle = Triangle::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            white(),
+        );
+        let ray = Ray::new(Vector3::new(0.25, 0.5, 2.0), Vector3::new(0.0, 0.0, -1.0));
+
+        let hit = triangle
+            .hit(&ray, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+            .unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-12);
+        assert_eq!(hit.normal, Vector3::new(0.0, 0.0, 1.0));
+        assert!((hit.u - 0.25).abs() < 1e-12);
+        assert!((hit.v - 0.5).abs() < 1e-12);
+
+        let outside = Ray::new(Vector3::new(0.75, 0.5, 2.0), Vector3::new(0.0, 0.0, -1.0));
+        assert!(triangle
+            .hit(&outside, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+            .is_none());
+    }
+
+    #[test]
+    fn mesh_interpolates_normals_and_uvs() {
+        let mesh = TriangleMesh {
+            positions: vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ],
+            normals: vec![
+                Vector3::new(0.0, 0.0, 1.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ],
+            uvs: vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            materials: vec![white()],
+            faces: vec![Face {
+                positions: [0, 1, 2],
+                normals: Some([0, 1, 2]),
+                uvs: Some([0, 1, 2]),
+                material: 0,
+            }],
+        };
+        let triangle = mesh.into_triangles().pop().unwrap();
+
+        // Halfway along the edge between the second and third vertices.
+        let ray = Ray::new(Vector3::new(0.5, 0.5, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = triangle
+            .hit(&ray, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+            .unwrap();
+
+        let expected = Vector3::new(1.0, 1.0, 0.0).normalize();
+        assert!((hit.normal - expected).magnitude() < 1e-12);
+        assert!((hit.u - 1.0).abs() < 1e-12);
+        assert!((hit.v - 0.5).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,383 @@
+// Microfacet reflection
+//
+// Rough surfaces are modelled as a mass of tiny mirror facets whose normals follow the GGX
+// (Trowbridge-Reitz) distribution, with Smith's height-correlated masking-shadowing for the
+// facets hidden from view or from the light. Directions are in a local frame with the surface
+// normal along +z; see `Onb`.
+
+use super::Sampler;
+use cgmath::{prelude::*, Vector3};
+use serde::Deserialize;
+use std::f64::consts::PI;
+
+/// Below this alpha a surface is treated as perfectly smooth: the distribution is too sharp to
+/// evaluate usefully, and a mirror reflection is what it converges to anyway.
+const SMOOTH_ALPHA: f64 = 1e-3;
+
+/// An isotropic GGX distribution of facet normals.
+#[derive(Copy, Clone, Debug)]
+pub struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    /// `roughness` runs from 0 for a mirror to 1 for a very rough surface; it's squared to give
+    /// the distribution's alpha, which makes the roughness look roughly linear.
+    pub fn new(roughness: f64) -> Ggx {
+        Ggx {
+            alpha: roughness * roughness,
+        }
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < SMOOTH_ALPHA
+    }
+
+    /// Density of facet normals `m`, per unit solid angle, projected onto the surface: it
+    /// integrates to one against `m.z`.
+    pub fn d(&self, m: Vector3<f64>) -> f64 {
+        if m.z <= 0.0 {
+            return 0.0;
+        }
+
+        let alpha2 = self.alpha * self.alpha;
+        let t = m.z * m.z * (alpha2 - 1.0) + 1.0;
+
+        alpha2 / (PI * t * t)
+    }
+
+    fn lambda(&self, w: Vector3<f64>) -> f64 {
+        let cos2 = w.z * w.z;
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Fraction of the facets facing `w` that are visible from it.
+    pub fn g1(&self, w: Vector3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Fraction of facets visible from both `wo` and `wi`.
+    pub fn g(&self, wo: Vector3<f64>, wi: Vector3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of the facet normals seen from `wo`, as `sample_visible` picks them.
+    pub fn visible_pdf(&self, wo: Vector3<f64>, m: Vector3<f64>) -> f64 {
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+
+        self.g1(wo) * wo.dot(m).max(0.0) * self.d(m) / wo.z
+    }
+
+    /// Picks a facet normal in proportion to how much of it is visible from `wo`, so that no
+    /// samples are wasted on facets facing away (Heitz, "Sampling the GGX Distribution of
+    /// Visible Normals", 2018).
+    pub fn sample_visible(&self, wo: Vector3<f64>, sampler: &mut Sampler) -> Vector3<f64> {
+        // Stretch the view direction to where the distribution is a hemisphere.
+        let vh = Vector3::new(self.alpha * wo.x, self.alpha * wo.y, wo.z).normalize();
+
+        let length2 = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if length2 > 0.0 {
+            Vector3::new(-vh.y, vh.x, 0.0) / length2.sqrt()
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+
+        // A point on the disk the hemisphere projects to, squashed onto the part facing vh.
+        let r = sampler.next_f64().sqrt();
+        let phi = 2.0 * PI * sampler.next_f64();
+        let p1 = r * phi.cos();
+        let s = (1.0 + vh.z) / 2.0;
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+        // And back again.
+        Vector3::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(0.0)).normalize()
+    }
+
+    /// For light reflected from `wi` into `wo`: the facet normal that reflects it, the BRDF
+    /// short of the Fresnel term, and the density of picking `wi` by reflecting `wo` about a
+    /// `sample_visible` normal.
+    pub fn reflection(
+        &self,
+        wo: Vector3<f64>,
+        wi: Vector3<f64>,
+    ) -> Option<(Vector3<f64>, f64, f64)> {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return None;
+        }
+
+        let m = (wo + wi).normalize();
+        let brdf = self.d(m) * self.g(wo, wi) / (4.0 * wo.z * wi.z);
+        let pdf = self.visible_pdf(wo, m) / (4.0 * wo.dot(m));
+
+        Some((m, brdf, pdf))
+    }
+
+    /// Picks the direction light leaving a dielectric boundary along `wo` came from, reflected
+    /// or refracted in proportion to the Fresnel equations. `eta` is how many times denser the
+    /// medium below the surface is. Smooth surfaces reflect and refract about the normal.
+    pub fn sample_dielectric(
+        &self,
+        wo: Vector3<f64>,
+        eta: f64,
+        sampler: &mut Sampler,
+    ) -> Option<Vector3<f64>> {
+        let m = if self.is_smooth() {
+            Vector3::unit_z()
+        } else {
+            self.sample_visible(wo, sampler)
+        };
+
+        let reflectance = fresnel_dielectric(wo.dot(m), eta);
+        let (wi, refracted) = match refract_through(wo, m, eta) {
+            Some(refracted) if sampler.next_f64() >= reflectance => (refracted, true),
+            _ => (reflect_about(wo, m), false),
+        };
+
+        // A rough facet can send the ray out the wrong side of the surface.
+        if wi.z == 0.0 || (wi.z < 0.0) != refracted {
+            return None;
+        }
+
+        Some(wi)
+    }
+
+    /// The BSDF of a rough dielectric boundary for light from `wi` leaving along `wo`, and the
+    /// density with which `sample_dielectric` picks `wi` (Walter et al., "Microfacet Models for
+    /// Refraction through Rough Surfaces", 2007).
+    pub fn dielectric(&self, wo: Vector3<f64>, wi: Vector3<f64>, eta: f64) -> Option<(f64, f64)> {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return None;
+        }
+
+        if wi.z > 0.0 {
+            let (m, brdf, pdf) = self.reflection(wo, wi)?;
+            let reflectance = fresnel_dielectric(wo.dot(m), eta);
+
+            return Some((reflectance * brdf, reflectance * pdf));
+        }
+
+        // The facet normal that refracts wo into wi.
+        let half = wo + wi * eta;
+        if half.magnitude2() < 1e-12 {
+            return None;
+        }
+        let m = if half.z < 0.0 { -half } else { half }.normalize();
+        if wo.dot(m) <= 0.0 || wi.dot(m) >= 0.0 {
+            return None;
+        }
+
+        let transmittance = 1.0 - fresnel_dielectric(wo.dot(m), eta);
+        let denominator = wo.dot(m) + eta * wi.dot(m);
+        // How much the solid angle of wi is squeezed relative to that of m.
+        let jacobian = eta * eta * wi.dot(m).abs() / (denominator * denominator);
+
+        let d = self.d(m);
+        let g = self.g(wo, wi);
+        let btdf = transmittance * d * g * wo.dot(m) * jacobian / (wo.z * wi.z.abs());
+        let pdf = transmittance * self.visible_pdf(wo, m) * jacobian;
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some((btdf, pdf))
+    }
+}
+
+/// Mirror `w` about the normal `m`.
+pub fn reflect_about(w: Vector3<f64>, m: Vector3<f64>) -> Vector3<f64> {
+    m * 2.0 * w.dot(m) - w
+}
+
+/// Bends `w` through a facet with normal `m` on the same side, into a medium `eta` times as
+/// dense. Returns `None` on total internal reflection.
+pub fn refract_through(w: Vector3<f64>, m: Vector3<f64>, eta: f64) -> Option<Vector3<f64>> {
+    let cos_i = w.dot(m);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    Some(-w / eta + m * (cos_i / eta - cos_t))
+}
+
+/// Fraction of unpolarized light reflected by a boundary into a medium `eta` times as dense,
+/// arriving at an angle with cosine `cos_i`.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+
+    (parallel * parallel + perpendicular * perpendicular) / 2.0
+}
+
+/// Fraction of unpolarized light a metal reflects, from its complex refractive index `eta + ik`
+/// for each color channel.
+pub fn fresnel_conductor(cos_i: f64, eta: Vector3<f64>, k: Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(
+        conductor_reflectance(cos_i, eta.x, k.x),
+        conductor_reflectance(cos_i, eta.y, k.y),
+        conductor_reflectance(cos_i, eta.z, k.z),
+    )
+}
+
+fn conductor_reflectance(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = ((a2_plus_b2 + t0) / 2.0).max(0.0).sqrt();
+    let t2 = 2.0 * cos_i * a;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+
+    (parallel + perpendicular) / 2.0
+}
+
+/// Measured optical constants for common metals, at roughly the wavelengths of the red, green
+/// and blue channels (650, 550 and 450 nm).
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metal {
+    Gold,
+    Copper,
+    #[serde(alias = "aluminum")]
+    Aluminium,
+}
+
+impl Metal {
+    /// Real part of the refractive index.
+    pub fn eta(&self) -> Vector3<f64> {
+        match self {
+            Metal::Gold => Vector3::new(0.143, 0.374, 1.442),
+            Metal::Copper => Vector3::new(0.200, 0.924, 1.102),
+            Metal::Aluminium => Vector3::new(1.657, 0.880, 0.521),
+        }
+    }
+
+    /// Extinction coefficient: the imaginary part of the refractive index.
+    pub fn k(&self) -> Vector3<f64> {
+        match self {
+            Metal::Gold => Vector3::new(3.983, 2.385, 1.603),
+            Metal::Copper => Vector3::new(3.912, 2.452, 2.142),
+            Metal::Aluminium => Vector3::new(9.224, 6.270, 4.837),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solas::{random_in_unit_sphere, DEFAULT_SEED};
+
+    /// Monte Carlo estimate of the integral of `f` over the upper hemisphere.
+    fn integrate(f: impl Fn(Vector3<f64>) -> f64) -> f64 {
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+        let count = 400_000;
+        let mut total = 0.0;
+        for _ in 0..count {
+            let mut w = random_in_unit_sphere(&mut sampler).normalize();
+            w.z = w.z.abs();
+            total += f(w);
+        }
+
+        total * 2.0 * PI / count as f64
+    }
+
+    #[test]
+    fn distribution_is_normalized() {
+        for roughness in [0.3, 0.6, 1.0] {
+            let ggx = Ggx::new(roughness);
+            let integral = integrate(|m| ggx.d(m) * m.z);
+            assert!((integral - 1.0).abs() < 0.02, "{} {}", roughness, integral);
+        }
+    }
+
+    #[test]
+    fn visible_normals_match_their_density() {
+        let ggx = Ggx::new(0.6);
+        let wo = Vector3::new(0.6, 0.0, 0.8);
+
+        let integral = integrate(|m| ggx.visible_pdf(wo, m));
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+
+        // The sampled normals' mean should match the density's.
+        let mut sampler = Sampler::new(DEFAULT_SEED, 1);
+        let count = 200_000;
+        let mut mean = Vector3::new(0.0, 0.0, 0.0);
+        for _ in 0..count {
+            let m = ggx.sample_visible(wo, &mut sampler);
+            assert!(wo.dot(m) >= 0.0);
+            mean += m / count as f64;
+        }
+        let expected = Vector3::new(
+            integrate(|m| ggx.visible_pdf(wo, m) * m.x),
+            integrate(|m| ggx.visible_pdf(wo, m) * m.y),
+            integrate(|m| ggx.visible_pdf(wo, m) * m.z),
+        );
+        assert!(
+            (mean - expected).magnitude() < 0.02,
+            "{:?} {:?}",
+            mean,
+            expected
+        );
+    }
+
+    #[test]
+    fn refraction_follows_snell() {
+        let m = Vector3::new(0.0, 0.0, 1.0);
+        let w = Vector3::new(0.6, 0.0, 0.8);
+        let refracted = refract_through(w, m, 1.5).unwrap();
+
+        assert!((refracted.magnitude() - 1.0).abs() < 1e-12);
+        assert!((-refracted.x * 1.5 - w.x).abs() < 1e-12);
+        assert!(refract_through(w, m, 1.0 / 1.5).is_some());
+        assert!(refract_through(Vector3::new(0.8, 0.0, 0.6), m, 1.0 / 1.5).is_none());
+    }
+
+    #[test]
+    fn fresnel_reflectance() {
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-9);
+        assert_eq!(fresnel_dielectric(0.5, 1.0 / 1.5), 1.0);
+        assert!((fresnel_dielectric(0.0, 1.5) - 1.0).abs() < 1e-9);
+
+        // A conductor with no extinction is just a dielectric.
+        let eta = Vector3::new(1.5, 1.5, 1.5);
+        let zero = Vector3::new(0.0, 0.0, 0.0);
+        for cos in [0.1, 0.5, 1.0] {
+            let f = fresnel_conductor(cos, eta, zero);
+            assert!((f.x - fresnel_dielectric(cos, 1.5)).abs() < 1e-9);
+        }
+
+        // Gold is yellow, and metals reflect everything at grazing angles.
+        let gold = fresnel_conductor(1.0, Metal::Gold.eta(), Metal::Gold.k());
+        assert!(gold.x > 0.9 && gold.x > gold.y && gold.y > gold.z);
+        for metal in [Metal::Gold, Metal::Copper, Metal::Aluminium] {
+            let grazing = fresnel_conductor(0.0, metal.eta(), metal.k());
+            assert!((grazing - Vector3::new(1.0, 1.0, 1.0)).magnitude() < 1e-9);
+        }
+    }
+}
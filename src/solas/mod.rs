@@ -1,16 +1,67 @@
+mod aabb;
+mod accumulator;
+mod bvh;
 mod camera;
+mod checkpoint;
 mod color;
 mod extensions;
+mod filter;
+mod framebuffer;
 mod intersections;
+mod light;
 mod material;
+mod mesh;
+mod microfacet;
+mod obj;
+mod onb;
+mod perlin;
+mod principled;
+mod quad;
 mod ray;
+mod sampler;
+mod scene;
+mod scene_file;
+mod texture;
+mod tonemap;
+mod transform;
+mod volume;
 
+pub use aabb::Aabb;
+pub use accumulator::{Accumulator, PixelStats};
+pub use bvh::Bvh;
 pub use camera::Camera;
-pub use color::Color;
+pub use checkpoint::Checkpoint;
 pub use extensions::{RgbExt, VectorExt};
-pub use intersections::{hit, Hit, Sphere};
+pub use filter::Filter;
+pub use framebuffer::{Framebuffer, OutputFormat};
+pub use intersections::{Hit, Hittable, HittableList, MovingSphere, Sphere};
+pub use light::{power_heuristic, LightList};
 pub use material::{
-    make_dialectric, make_lambertian, make_metal, DialectricMaterial, LambertianMaterial, Material,
-    MetalMaterial,
+    make_conductor, make_dialectric, make_diffuse_light, make_isotropic, make_lambertian,
+    make_metal, make_principled, make_rough_dielectric, make_textured_lambertian,
+    make_textured_metal, make_textured_principled, Material, Scatter,
 };
+// The material types themselves, for code that wants more than the make_* helpers; the renderer
+// doesn't need all of them.
+#[allow(unused_imports)]
+pub use material::{
+    ConductorMaterial, DialectricMaterial, DiffuseLight, IsotropicMaterial, LambertianMaterial,
+    MetalMaterial, RoughDielectricMaterial,
+};
+pub use mesh::{Face, Triangle, TriangleMesh};
+pub use microfacet::{fresnel_conductor, reflect_about, Ggx, Metal};
+pub use obj::load_obj;
+pub use onb::{random_cosine_direction, Onb};
+pub use perlin::Perlin;
+pub use principled::{Principled, PrincipledMaterial};
+pub use quad::{Cuboid, Quad};
 pub use ray::{random_in_unit_sphere, Ray};
+pub use sampler::{Sampler, DEFAULT_SEED};
+pub use scene::{Background, Scene};
+pub use scene_file::{load_scene, RenderOptions, SceneDescription};
+pub use texture::{
+    Checker, ImageTexture, MarbleTexture, NoiseTexture, SolidColor, Texture, WoodTexture, WrapMode,
+};
+pub use tonemap::{OutputTransform, ToneMap};
+pub use transform::Instance;
+pub use volume::{ConstantMedium, Fog};
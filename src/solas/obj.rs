@@ -0,0 +1,272 @@
+// Wavefront OBJ loading
+//
+// Reads positions (v), normals (vn), texture coordinates (vt) and polygonal faces (f), which
+// are split into triangles as a fan. Each `usemtl` name is looked up in the materials passed
+// in, and must be there; faces before the first `usemtl` get the default. Anything else
+// (groups, smoothing, .mtl libraries, ...) is ignored.
+
+use super::*;
+use cgmath::Vector3;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(std::io::Error),
+    Invalid { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(error) => write!(f, "could not read OBJ file: {}", error),
+            ObjError::Invalid { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+pub fn load_obj(
+    path: &Path,
+    default: Arc<dyn Material>,
+    materials: &BTreeMap<&str, Arc<dyn Material>>,
+) -> Result<TriangleMesh, ObjError> {
+    let text = fs::read_to_string(path).map_err(ObjError::Io)?;
+
+    parse_obj(&text, default, materials)
+}
+
+pub fn parse_obj(
+    text: &str,
+    default: Arc<dyn Material>,
+    materials: &BTreeMap<&str, Arc<dyn Material>>,
+) -> Result<TriangleMesh, ObjError> {
+    let mut mesh = TriangleMesh {
+        materials: vec![default],
+        ..Default::default()
+    };
+    // Index into mesh.materials of each material used so far, so faces can share them.
+    let mut used: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut material = 0;
+
+    for (number, line) in text.lines().enumerate() {
+        let invalid = |message: String| ObjError::Invalid {
+            line: number + 1,
+            message,
+        };
+
+        let line = line.split('#').next().unwrap_or("");
+        let mut fields = line.split_whitespace();
+        let keyword = match fields.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let fields: Vec<&str> = fields.collect();
+
+        match keyword {
+            "v" | "vn" => {
+                let v = numbers(&fields, 3, keyword).map_err(invalid)?;
+                let v = Vector3::new(v[0], v[1], v[2]);
+
+                if keyword == "v" {
+                    mesh.positions.push(v);
+                } else {
+                    mesh.normals.push(v);
+                }
+            }
+            "vt" => {
+                // The v coordinate is optional, and any w is ignored.
+                let uv = numbers(&fields, 1, keyword).map_err(invalid)?;
+                mesh.uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            }
+            "f" => {
+                if fields.len() < 3 {
+                    return Err(invalid(format!(
+                        "a face needs at least 3 vertices, got {}",
+                        fields.len()
+                    )));
+                }
+
+                let vertices = fields
+                    .iter()
+                    .map(|field| vertex(field, &mesh))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(invalid)?;
+
+                for i in 1..vertices.len() - 1 {
+                    let corners = [vertices[0], vertices[i], vertices[i + 1]];
+
+                    mesh.faces.push(Face {
+                        positions: corners.map(|corner| corner.0),
+                        normals: all(corners.map(|corner| corner.2)),
+                        uvs: all(corners.map(|corner| corner.1)),
+                        material,
+                    });
+                }
+            }
+            "usemtl" => {
+                let name = fields
+                    .first()
+                    .ok_or_else(|| invalid("usemtl needs a material name".to_string()))?;
+
+                material = match (used.get(name), materials.get(name)) {
+                    (Some(index), _) => *index,
+                    (None, Some(found)) => {
+                        mesh.materials.push(found.clone());
+                        used.insert(name, mesh.materials.len() - 1);
+                        mesh.materials.len() - 1
+                    }
+                    (None, None) => {
+                        return Err(invalid(format!("unknown material \"{}\"", name)));
+                    }
+                };
+            }
+            _ => {}
+        }
+    }
+
+    Ok(mesh)
+}
+
+fn numbers(fields: &[&str], count: usize, keyword: &str) -> Result<Vec<f64>, String> {
+    if fields.len() < count {
+        return Err(format!(
+            "{} needs {} numbers, got {}",
+            keyword,
+            count,
+            fields.len()
+        ));
+    }
+
+    fields
+        .iter()
+        .map(|field| {
+            field
+                .parse()
+                .map_err(|_| format!("{}: \"{}\" is not a number", keyword, field))
+        })
+        .collect()
+}
+
+/// Parses one face vertex, `v`, `v/vt`, `v//vn` or `v/vt/vn`, into zero-based indices.
+fn vertex(
+    field: &str,
+    mesh: &TriangleMesh,
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = field.split('/');
+
+    let position = parts.next().unwrap_or("");
+    let position = index(position, mesh.positions.len(), "vertex")?;
+
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(uv) => Some(index(uv, mesh.uvs.len(), "texture coordinate")?),
+    };
+
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(normal) => Some(index(normal, mesh.normals.len(), "normal")?),
+    };
+
+    Ok((position, uv, normal))
+}
+
+/// OBJ indices start at 1; negative ones count back from the most recent element.
+fn index(field: &str, count: usize, what: &str) -> Result<usize, String> {
+    let index: i64 = field
+        .parse()
+        .map_err(|_| format!("\"{}\" is not a valid {} index", field, what))?;
+    let count = count as i64;
+
+    let resolved = if index < 0 { count + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= count {
+        return Err(format!(
+            "{} index {} is out of range (there are {})",
+            what, index, count
+        ));
+    }
+
+    Ok(resolved as usize)
+}
+
+/// A face only gets normals or uvs if every one of its corners has them.
+fn all(indices: [Option<usize>; 3]) -> Option<[usize; 3]> {
+    match indices {
+        [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "
+# A unit square in the xy plane, as one quad.
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+
+f -4//1 -3//1 -2//1
+usemtl red
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+
+    #[test]
+    fn parses_faces_and_materials() {
+        let red = make_lambertian(Vector3::new(0.8, 0.1, 0.1));
+        let default = make_lambertian(Vector3::new(0.5, 0.5, 0.5));
+        let materials = BTreeMap::from([("red", red)]);
+
+        let mesh = parse_obj(SQUARE, default, &materials).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.faces.len(), 3);
+        assert_eq!(mesh.materials.len(), 2);
+
+        let first = mesh.faces[0];
+        assert_eq!(first.positions, [0, 1, 2]);
+        assert_eq!(first.normals, Some([0, 0, 0]));
+        assert_eq!(first.uvs, None);
+        assert_eq!(first.material, 0);
+
+        let quad = &mesh.faces[1..];
+        assert_eq!(quad[0].positions, [0, 1, 2]);
+        assert_eq!(quad[1].positions, [0, 2, 3]);
+        assert_eq!(quad[1].uvs, Some([0, 2, 3]));
+        assert!(quad.iter().all(|face| face.material == 1));
+    }
+
+    #[test]
+    fn unknown_material_reports_line() {
+        let default = make_lambertian(Vector3::new(0.5, 0.5, 0.5));
+
+        let message = parse_obj(SQUARE, default, &BTreeMap::new())
+            .err()
+            .unwrap()
+            .to_string();
+        assert_eq!(message, "line 14: unknown material \"red\"");
+    }
+
+    #[test]
+    fn bad_index_reports_line() {
+        let text = SQUARE.replace("f -4//1", "f 7//1");
+        let default = make_lambertian(Vector3::new(0.5, 0.5, 0.5));
+
+        let message = parse_obj(&text, default, &BTreeMap::new())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(message.starts_with("line 13:"), "{}", message);
+        assert!(message.contains("vertex index 7"), "{}", message);
+    }
+}
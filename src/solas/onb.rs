@@ -0,0 +1,98 @@
+// Orthonormal bases
+
+use super::Sampler;
+use cgmath::{prelude::*, Vector3};
+use std::f64::consts::PI;
+
+/// Three perpendicular unit vectors with `w` along a chosen direction, such as a surface normal
+/// or the way to a light, so directions can be sampled around it.
+#[derive(Copy, Clone, Debug)]
+pub struct Onb {
+    pub u: Vector3<f64>,
+    pub v: Vector3<f64>,
+    pub w: Vector3<f64>,
+}
+
+impl Onb {
+    pub fn new(direction: Vector3<f64>) -> Onb {
+        let w = direction.normalize();
+        // Any vector not too close to w will do to build the other two from.
+        let a = if w.x.abs() > 0.9 {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).normalize();
+        let u = w.cross(v);
+
+        Onb { u, v, w }
+    }
+
+    /// Turns coordinates in this basis into a world space vector.
+    pub fn local(&self, a: Vector3<f64>) -> Vector3<f64> {
+        self.u * a.x + self.v * a.y + self.w * a.z
+    }
+
+    /// The coordinates of a world space vector in this basis: the inverse of `local`.
+    pub fn coordinates(&self, a: Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
+
+/// A direction in the hemisphere around +z, picked with a density of cos θ / π: the shape of
+/// a diffuse surface's scattering. Turn it into world space with `Onb::local`.
+pub fn random_cosine_direction(sampler: &mut Sampler) -> Vector3<f64> {
+    let r1 = sampler.next_f64();
+    let r2 = sampler.next_f64();
+
+    let phi = 2.0 * PI * r1;
+    let radius = r2.sqrt();
+
+    Vector3::new(phi.cos() * radius, phi.sin() * radius, (1.0 - r2).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solas::DEFAULT_SEED;
+
+    #[test]
+    fn basis_is_orthonormal() {
+        for direction in [
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(-3.0, 2.0, 0.5),
+        ] {
+            let onb = Onb::new(direction);
+
+            for axis in [onb.u, onb.v, onb.w] {
+                assert!((axis.magnitude() - 1.0).abs() < 1e-12);
+            }
+            assert!(onb.u.dot(onb.v).abs() < 1e-12);
+            assert!(onb.v.dot(onb.w).abs() < 1e-12);
+            assert!(onb.w.dot(onb.u).abs() < 1e-12);
+            assert!((onb.w - direction.normalize()).magnitude() < 1e-12);
+            assert!((onb.local(Vector3::new(0.0, 0.0, 2.0)) - onb.w * 2.0).magnitude() < 1e-12);
+
+            let a = Vector3::new(0.3, -1.2, 0.7);
+            assert!((onb.local(onb.coordinates(a)) - a).magnitude() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn cosine_directions_favor_the_pole() {
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+        let count = 100_000;
+        let mut total = 0.0;
+
+        for _ in 0..count {
+            let direction = random_cosine_direction(&mut sampler);
+            assert!((direction.magnitude() - 1.0).abs() < 1e-9);
+            assert!(direction.z >= 0.0);
+            total += direction.z;
+        }
+
+        // The mean of cos θ under a cos θ / π density is 2/3.
+        assert!((total / count as f64 - 2.0 / 3.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,146 @@
+// Perlin noise
+
+use super::Sampler;
+use cgmath::{prelude::*, Vector3};
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise: smooth, repeatable pseudo-random values in [-1, 1] that vary continuously
+/// through space, for procedural textures.
+pub struct Perlin {
+    gradients: Vec<Vector3<f64>>,
+    permutations: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    /// The same seed always gives the same noise.
+    pub fn new(seed: u64) -> Perlin {
+        let mut sampler = Sampler::new(seed, 0);
+
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                let v = Vector3::new(
+                    sampler.next_f64() * 2.0 - 1.0,
+                    sampler.next_f64() * 2.0 - 1.0,
+                    sampler.next_f64() * 2.0 - 1.0,
+                );
+                let length = v.magnitude();
+                if length > 1e-3 && length <= 1.0 {
+                    break v / length;
+                }
+            })
+            .collect();
+
+        let permutations = [(); 3].map(|_| permutation(&mut sampler));
+
+        Perlin {
+            gradients,
+            permutations,
+        }
+    }
+
+    pub fn noise(&self, p: Vector3<f64>) -> f64 {
+        let floor = p.map(f64::floor);
+        let fraction = p - floor;
+        let (i, j, k) = (floor.x as i64, floor.y as i64, floor.z as i64);
+
+        // Gradients at the eight corners of the lattice cell around p.
+        let mut corners = [[[Vector3::new(0.0, 0.0, 0.0); 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let [x, y, z] = &self.permutations;
+                    let index =
+                        x[wrap(i + di as i64)] ^ y[wrap(j + dj as i64)] ^ z[wrap(k + dk as i64)];
+                    *corner = self.gradients[index];
+                }
+            }
+        }
+
+        interpolate(&corners, fraction)
+    }
+
+    /// Sum of `depth` octaves of noise, each at twice the frequency and half the weight of the
+    /// one before. Always positive.
+    pub fn turbulence(&self, p: Vector3<f64>, depth: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            sum += weight * self.noise(p);
+            weight *= 0.5;
+            p *= 2.0;
+        }
+
+        sum.abs()
+    }
+}
+
+fn permutation(sampler: &mut Sampler) -> Vec<usize> {
+    let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+
+    // Fisher-Yates shuffle.
+    for i in (1..POINT_COUNT).rev() {
+        let target = (sampler.next_u32() as usize) % (i + 1);
+        p.swap(i, target);
+    }
+
+    p
+}
+
+fn wrap(index: i64) -> usize {
+    (index & (POINT_COUNT as i64 - 1)) as usize
+}
+
+/// Trilinear interpolation of the corner gradients' contributions, eased with a Hermite cubic
+/// so the noise has no visible grid artifacts.
+fn interpolate(corners: &[[[Vector3<f64>; 2]; 2]; 2], fraction: Vector3<f64>) -> f64 {
+    let hermite = fraction.map(|t| t * t * (3.0 - 2.0 * t));
+    let mut sum = 0.0;
+
+    for (i, plane) in corners.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, gradient) in row.iter().enumerate() {
+                let (i, j, k) = (i as f64, j as f64, k as f64);
+                let weight = fraction - Vector3::new(i, j, k);
+
+                sum += (i * hermite.x + (1.0 - i) * (1.0 - hermite.x))
+                    * (j * hermite.y + (1.0 - j) * (1.0 - hermite.y))
+                    * (k * hermite.z + (1.0 - k) * (1.0 - hermite.z))
+                    * gradient.dot(weight);
+            }
+        }
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solas::DEFAULT_SEED;
+
+    #[test]
+    fn noise_is_seeded() {
+        let p = Vector3::new(1.3, -4.7, 2.1);
+
+        assert_eq!(Perlin::new(1).noise(p), Perlin::new(1).noise(p));
+        assert_ne!(Perlin::new(1).noise(p), Perlin::new(2).noise(p));
+    }
+
+    #[test]
+    fn noise_is_bounded_and_vanishes_on_the_lattice() {
+        let perlin = Perlin::new(DEFAULT_SEED);
+        let mut sampler = Sampler::new(DEFAULT_SEED, 1);
+
+        for _ in 0..1000 {
+            let p = Vector3::new(sampler.next_f64(), sampler.next_f64(), sampler.next_f64());
+            let noise = perlin.noise((p - Vector3::new(0.5, 0.5, 0.5)) * 20.0);
+            assert!((-1.0..=1.0).contains(&noise));
+        }
+
+        assert_eq!(perlin.noise(Vector3::new(3.0, -2.0, 7.0)), 0.0);
+        assert!(perlin.turbulence(Vector3::new(0.3, 0.2, 0.1), 7) >= 0.0);
+    }
+}
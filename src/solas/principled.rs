@@ -0,0 +1,477 @@
+// Principled material
+//
+// One material covering most real surfaces, after Burley's "Physically Based Shading at Disney"
+// (2012) and its 2015 extension to transmission. It blends a diffuse base with subsurface and
+// sheen terms, a GGX highlight that goes from a dielectric's to a metal's, rough glass and a
+// clear coat. Each scattered direction comes from one of those lobes, picked at random, and is
+// weighted by the density of all of them together.
+
+use super::*;
+use cgmath::{prelude::*, Vector3};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// The specular lobes can't get any sharper than this: they have to be evaluated for any
+/// direction to be mixed with the others, which a perfect mirror can't be. Use a conductor or
+/// a dielectric for those.
+const MIN_ROUGHNESS: f64 = 0.05;
+
+/// Head-on reflectance of the clear coat: a refractive index of 1.5.
+const CLEARCOAT_REFLECTANCE: f64 = 0.04;
+
+/// The lobes with the least weight still get at least this share of it when picking one, so
+/// that faint highlights aren't left to chance entirely.
+const MIN_LOBE_WEIGHT: f64 = 0.25;
+
+/// The parameters of a `PrincipledMaterial` besides its base color. They all run from 0 to 1,
+/// except the refractive index.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Principled {
+    /// Blends from a dielectric, like plastic, to a metal with the base color as reflectance.
+    pub metallic: f64,
+    pub roughness: f64,
+    /// How strongly a dielectric reflects head on, as a fraction of 8%; the default of 0.5 is
+    /// right for most materials.
+    pub specular: f64,
+    /// A second, clear and glossy layer on top, like the lacquer of car paint.
+    pub clearcoat: f64,
+    pub clearcoat_roughness: f64,
+    /// A bright rim at grazing angles, like that of cloth.
+    pub sheen: f64,
+    /// Blends from an opaque surface to rough glass tinted by the base color.
+    pub transmission: f64,
+    /// Flattens the diffuse falloff as light scattered under the surface does, like that of
+    /// skin or wax.
+    pub subsurface: f64,
+    /// Of the glass that `transmission` blends to.
+    pub refractive_index: f64,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Principled {
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.1,
+            sheen: 0.0,
+            transmission: 0.0,
+            subsurface: 0.0,
+            refractive_index: 1.5,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PrincipledMaterial {
+    base_color: Arc<dyn Texture>,
+    parameters: Principled,
+    specular: Ggx,
+    clearcoat: Ggx,
+}
+
+/// How likely each lobe is to be picked for a scattered direction.
+struct LobeChances {
+    diffuse: f64,
+    specular: f64,
+    clearcoat: f64,
+    transmission: f64,
+}
+
+/// Schlick's approximation of the Fresnel reflectance for a head-on reflectance of `f0`.
+fn schlick(f0: Vector3<f64>, cosine: f64) -> Vector3<f64> {
+    let ones = Vector3::new(1.0, 1.0, 1.0);
+
+    f0 + (ones - f0) * schlick_weight(cosine)
+}
+
+fn schlick_weight(cosine: f64) -> f64 {
+    (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+impl PrincipledMaterial {
+    pub fn new(base_color: Arc<dyn Texture>, parameters: Principled) -> Self {
+        PrincipledMaterial {
+            base_color,
+            parameters,
+            specular: Ggx::new(parameters.roughness.max(MIN_ROUGHNESS)),
+            clearcoat: Ggx::new(parameters.clearcoat_roughness.max(MIN_ROUGHNESS)),
+        }
+    }
+
+    /// The share of the surface that's an opaque dielectric, with a diffuse base, and the share
+    /// that's glass. The rest is metal.
+    fn dielectric_weights(&self) -> (f64, f64) {
+        let Principled {
+            metallic,
+            transmission,
+            ..
+        } = self.parameters;
+
+        (
+            (1.0 - metallic) * (1.0 - transmission),
+            (1.0 - metallic) * transmission,
+        )
+    }
+
+    /// The Fresnel reflectance of the highlight: the metal's and the opaque dielectric's.
+    fn specular_reflectance(&self, base: Vector3<f64>, cosine: f64) -> Vector3<f64> {
+        let (opaque, _) = self.dielectric_weights();
+        let dielectric = Vector3::new(1.0, 1.0, 1.0) * (0.08 * self.parameters.specular);
+
+        schlick(base, cosine) * self.parameters.metallic + schlick(dielectric, cosine) * opaque
+    }
+
+    /// Picks lobes roughly in proportion to how much light they reflect towards `wo`.
+    fn lobe_chances(&self, base: Vector3<f64>, wo: Vector3<f64>) -> LobeChances {
+        let (opaque, glass) = self.dielectric_weights();
+        let specular = self.specular_reflectance(base, wo.z).to_color().luminance();
+        let clearcoat =
+            CLEARCOAT_REFLECTANCE + (1.0 - CLEARCOAT_REFLECTANCE) * schlick_weight(wo.z);
+
+        let diffuse = opaque;
+        let specular = specular.max(MIN_LOBE_WEIGHT * (1.0 - glass));
+        let clearcoat = self.parameters.clearcoat * clearcoat.max(MIN_LOBE_WEIGHT);
+        let total = diffuse + specular + clearcoat + glass;
+
+        LobeChances {
+            diffuse: diffuse / total,
+            specular: specular / total,
+            clearcoat: clearcoat / total,
+            transmission: glass / total,
+        }
+    }
+
+    /// How many times denser the medium on the far side of the surface is than the ray's.
+    fn relative_index(&self, ray: &Ray, hit: &Hit) -> f64 {
+        if ray.direction.dot(hit.normal) > 0.0 {
+            1.0 / self.parameters.refractive_index
+        } else {
+            self.parameters.refractive_index
+        }
+    }
+
+    /// The BSDF for light reflected from `wi` into `wo`, and the density of picking `wi`.
+    fn reflection(
+        &self,
+        base: Vector3<f64>,
+        wo: Vector3<f64>,
+        wi: Vector3<f64>,
+        eta: f64,
+        chances: &LobeChances,
+    ) -> (Vector3<f64>, f64) {
+        let p = &self.parameters;
+        let (opaque, glass) = self.dielectric_weights();
+        let mut f = Vector3::new(0.0, 0.0, 0.0);
+        let mut pdf = 0.0;
+
+        let half = (wo + wi).normalize();
+        let cos_d = wi.dot(half);
+
+        if opaque > 0.0 {
+            // Burley's diffuse, with its retro-reflection at grazing angles for rough surfaces,
+            // blended with the subsurface approximation based on Hanrahan-Krueger.
+            let (fl, fv) = (schlick_weight(wi.z), schlick_weight(wo.z));
+            let fd90 = 0.5 + 2.0 * p.roughness * cos_d * cos_d;
+            let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+            let fss90 = p.roughness * cos_d * cos_d;
+            let fss = (1.0 + (fss90 - 1.0) * fl) * (1.0 + (fss90 - 1.0) * fv);
+            let ss = 1.25 * (fss * (1.0 / (wi.z + wo.z) - 0.5) + 0.5);
+
+            let diffuse = base * (lerp(fd, ss, p.subsurface) / PI);
+            let sheen = Vector3::new(1.0, 1.0, 1.0) * (p.sheen * schlick_weight(cos_d));
+
+            f += (diffuse + sheen) * opaque;
+            pdf += chances.diffuse * wi.z / PI;
+        }
+
+        if let Some((m, brdf, lobe_pdf)) = self.specular.reflection(wo, wi) {
+            f += self.specular_reflectance(base, wi.dot(m)) * brdf;
+            pdf += chances.specular * lobe_pdf;
+        }
+
+        if p.clearcoat > 0.0 {
+            if let Some((m, brdf, lobe_pdf)) = self.clearcoat.reflection(wo, wi) {
+                let reflectance = CLEARCOAT_REFLECTANCE
+                    + (1.0 - CLEARCOAT_REFLECTANCE) * schlick_weight(wi.dot(m));
+
+                f += Vector3::new(1.0, 1.0, 1.0) * (p.clearcoat * reflectance * brdf);
+                pdf += chances.clearcoat * lobe_pdf;
+            }
+        }
+
+        if glass > 0.0 {
+            if let Some((bsdf, lobe_pdf)) = self.specular.dielectric(wo, wi, eta) {
+                f += Vector3::new(1.0, 1.0, 1.0) * (glass * bsdf);
+                pdf += chances.transmission * lobe_pdf;
+            }
+        }
+
+        (f, pdf)
+    }
+}
+
+impl Material for PrincipledMaterial {
+    fn scatter(&self, ray: &Ray, hit: &Hit, sampler: &mut Sampler) -> Option<Scatter> {
+        let (basis, wo) = hit.shading_frame(ray);
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let base = self.base_color.value(hit.u, hit.v, hit.p);
+        let chances = self.lobe_chances(base, wo);
+
+        let lobe = sampler.next_f64();
+        let wi = if lobe < chances.diffuse {
+            random_cosine_direction(sampler)
+        } else if lobe < chances.diffuse + chances.specular {
+            sample_reflection(&self.specular, wo, sampler)?
+        } else if lobe < chances.diffuse + chances.specular + chances.clearcoat {
+            sample_reflection(&self.clearcoat, wo, sampler)?
+        } else {
+            let eta = self.relative_index(ray, hit);
+            self.specular.sample_dielectric(wo, eta, sampler)?
+        };
+
+        self.evaluate(ray, hit, basis.local(wi))
+    }
+
+    fn evaluate(&self, ray: &Ray, hit: &Hit, direction: Vector3<f64>) -> Option<Scatter> {
+        let (basis, wo) = hit.shading_frame(ray);
+        let wi = basis.coordinates(direction.normalize());
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return None;
+        }
+        let base = self.base_color.value(hit.u, hit.v, hit.p);
+        let chances = self.lobe_chances(base, wo);
+        let eta = self.relative_index(ray, hit);
+
+        let (f, pdf) = if wi.z > 0.0 {
+            self.reflection(base, wo, wi, eta, &chances)
+        } else {
+            // Only the glass lets light through, tinted by the base color.
+            let (_, glass) = self.dielectric_weights();
+            let (btdf, pdf) = self.specular.dielectric(wo, wi, eta)?;
+            (base * (glass * btdf), chances.transmission * pdf)
+        };
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(Scatter {
+            ray: Ray::with_time(hit.p, basis.local(wi), ray.time),
+            f,
+            cosine: wi.z.abs(),
+            pdf,
+            specular: false,
+        })
+    }
+}
+
+/// Reflects `wo` about a visible facet of `distribution`. A rough facet can send it below the
+/// surface, where it would pass for transmitted light, so those samples are dropped.
+fn sample_reflection(
+    distribution: &Ggx,
+    wo: Vector3<f64>,
+    sampler: &mut Sampler,
+) -> Option<Vector3<f64>> {
+    let wi = reflect_about(wo, distribution.sample_visible(wo, sampler));
+
+    (wi.z > 0.0).then_some(wi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scatters a ray arriving at 45° from above many times, checking that `evaluate` agrees
+    /// with `scatter`, and returns the mean weight: the fraction of light reflected or
+    /// transmitted.
+    fn albedo(base: Vector3<f64>, parameters: Principled) -> Vector3<f64> {
+        let material = PrincipledMaterial::new(Arc::new(SolidColor::new(base)), parameters);
+        let direction = Vector3::new(1.0, -1.0, 0.0).normalize();
+        let ray = Ray::new(-direction, direction);
+        let origin = Vector3::new(0.0, 0.0, 0.0);
+        let hit = Hit::new(
+            1.0,
+            origin,
+            Vector3::new(0.0, 1.0, 0.0),
+            (0.0, 0.0),
+            &material,
+        );
+
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+        let count = 100_000;
+        let mut total = Vector3::new(0.0, 0.0, 0.0);
+        for _ in 0..count {
+            let Some(scatter) = material.scatter(&ray, &hit, &mut sampler) else {
+                continue;
+            };
+            let evaluated = material
+                .evaluate(&ray, &hit, scatter.ray.direction)
+                .unwrap();
+            assert!((evaluated.pdf - scatter.pdf).abs() < 1e-6 * scatter.pdf);
+            assert!(scatter.pdf > 0.0);
+
+            total += scatter.weight();
+        }
+
+        total / count as f64
+    }
+
+    #[test]
+    fn metals_reflect_their_base_color() {
+        let gold = Vector3::new(1.0, 0.8, 0.3);
+        let metal = Principled {
+            metallic: 1.0,
+            roughness: 0.3,
+            ..Default::default()
+        };
+
+        let reflected = albedo(gold, metal);
+        for channel in 0..3 {
+            assert!(
+                reflected[channel] <= gold[channel] + 0.05,
+                "{:?}",
+                reflected
+            );
+            assert!(
+                reflected[channel] >= gold[channel] - 0.15,
+                "{:?}",
+                reflected
+            );
+        }
+    }
+
+    #[test]
+    fn lobes_stay_close_to_energy_conserving() {
+        let white = Vector3::new(1.0, 1.0, 1.0);
+
+        for parameters in [
+            Principled::default(),
+            Principled {
+                clearcoat: 1.0,
+                sheen: 1.0,
+                subsurface: 1.0,
+                ..Default::default()
+            },
+            Principled {
+                transmission: 1.0,
+                roughness: 0.2,
+                ..Default::default()
+            },
+            Principled {
+                metallic: 0.5,
+                transmission: 0.5,
+                ..Default::default()
+            },
+        ] {
+            let reflected = albedo(white, parameters);
+            // Burley's diffuse and the layers on top of it aren't strictly energy conserving,
+            // but shouldn't make up much light.
+            assert!(
+                reflected.x > 0.5 && reflected.x < 1.15,
+                "{:?} {:?}",
+                parameters,
+                reflected
+            );
+        }
+    }
+
+    #[test]
+    fn scatter_weights_match_evaluate_from_inside() {
+        let material = PrincipledMaterial::new(
+            Arc::new(SolidColor::new(Vector3::new(0.8, 0.8, 0.8))),
+            Principled {
+                roughness: 0.9,
+                clearcoat: 1.0,
+                clearcoat_roughness: 0.9,
+                transmission: 0.5,
+                ..Default::default()
+            },
+        );
+        // Leaving glass at a grazing angle, where rough reflections often dip below the surface.
+        let direction = Vector3::new(1.0, 0.2, 0.0).normalize();
+        let ray = Ray::new(-direction, direction);
+        let hit = Hit::new(
+            1.0,
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            (0.0, 0.0),
+            &material,
+        );
+
+        // Light scattered back into the glass, and out through it.
+        let side = |direction: Vector3<f64>| (direction.y > 0.0) as usize;
+
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+        let count = 1_000_000;
+        let mut sampled = [0.0; 2];
+        for _ in 0..count {
+            if let Some(scatter) = material.scatter(&ray, &hit, &mut sampler) {
+                sampled[side(scatter.ray.direction)] += scatter.weight().x / count as f64;
+            }
+        }
+
+        // The same integrals of `evaluate`, over directions spread uniformly over the sphere
+        // one to a stratum.
+        let strata = 1000;
+        let solid_angle = 4.0 * PI / (strata * strata) as f64;
+        let mut uniform = [0.0; 2];
+        for i in 0..strata {
+            for j in 0..strata {
+                let z = 2.0 * (i as f64 + sampler.next_f64()) / strata as f64 - 1.0;
+                let phi = 2.0 * PI * (j as f64 + sampler.next_f64()) / strata as f64;
+                let r = (1.0 - z * z).sqrt();
+                let direction = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+                if let Some(evaluated) = material.evaluate(&ray, &hit, direction) {
+                    uniform[side(direction)] += evaluated.f.x * evaluated.cosine * solid_angle;
+                }
+            }
+        }
+
+        for (sampled, uniform) in sampled.into_iter().zip(uniform) {
+            assert!(
+                (sampled - uniform).abs() < 0.005 * uniform,
+                "{} {}",
+                sampled,
+                uniform
+            );
+        }
+    }
+
+    #[test]
+    fn only_glass_transmits() {
+        let material = PrincipledMaterial::new(
+            Arc::new(SolidColor::new(Vector3::new(0.8, 0.8, 0.8))),
+            Principled::default(),
+        );
+        let ray = Ray::new(Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let hit = Hit::new(
+            1.0,
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            (0.0, 0.0),
+            &material,
+        );
+
+        let below = Vector3::new(0.1, -1.0, 0.0);
+        assert!(material.evaluate(&ray, &hit, below).is_none());
+        assert!(material
+            .evaluate(&ray, &hit, Vector3::new(0.1, 1.0, 0.0))
+            .is_some());
+
+        let glass = PrincipledMaterial::new(
+            Arc::new(SolidColor::new(Vector3::new(0.8, 0.8, 0.8))),
+            Principled {
+                transmission: 1.0,
+                ..Default::default()
+            },
+        );
+        assert!(glass.evaluate(&ray, &hit, below).is_some());
+    }
+}
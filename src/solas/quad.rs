@@ -0,0 +1,299 @@
+// Quads, rectangles and boxes
+
+use super::*;
+use cgmath::{prelude::*, Vector3};
+use std::sync::Arc;
+
+/// A parallelogram with corner `q` and edges `u` and `v`. The normal points along `u × v`,
+/// and the hit's (u, v) are the coordinates along the two edges.
+#[derive(Clone)]
+pub struct Quad {
+    q: Vector3<f64>,
+    u: Vector3<f64>,
+    v: Vector3<f64>,
+    normal: Vector3<f64>,
+    d: f64,
+    w: Vector3<f64>,
+    area: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(
+        q: Vector3<f64>,
+        u: Vector3<f64>,
+        v: Vector3<f64>,
+        material: Arc<dyn Material>,
+    ) -> Quad {
+        let n = u.cross(v);
+        let normal = n.normalize();
+
+        Quad {
+            q,
+            u,
+            v,
+            normal,
+            d: normal.dot(q),
+            w: n / n.dot(n),
+            area: n.magnitude(),
+            material,
+        }
+    }
+
+    /// Rectangle in the plane z = k, facing +z.
+    pub fn xy_rect(
+        x0: f64,
+        x1: f64,
+        y0: f64,
+        y1: f64,
+        k: f64,
+        material: Arc<dyn Material>,
+    ) -> Quad {
+        Quad::new(
+            Vector3::new(x0, y0, k),
+            Vector3::new(x1 - x0, 0.0, 0.0),
+            Vector3::new(0.0, y1 - y0, 0.0),
+            material,
+        )
+    }
+
+    /// Rectangle in the plane y = k, facing +y.
+    pub fn xz_rect(
+        x0: f64,
+        x1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+        material: Arc<dyn Material>,
+    ) -> Quad {
+        Quad::new(
+            Vector3::new(x0, k, z0),
+            Vector3::new(0.0, 0.0, z1 - z0),
+            Vector3::new(x1 - x0, 0.0, 0.0),
+            material,
+        )
+    }
+
+    /// Rectangle in the plane x = k, facing +x.
+    pub fn yz_rect(
+        y0: f64,
+        y1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+        material: Arc<dyn Material>,
+    ) -> Quad {
+        Quad::new(
+            Vector3::new(k, y0, z0),
+            Vector3::new(0.0, y1 - y0, 0.0),
+            Vector3::new(0.0, 0.0, z1 - z0),
+            material,
+        )
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, min: f64, max: f64, _sampler: &mut Sampler) -> Option<Hit<'_>> {
+        let denominator = self.normal.dot(ray.direction);
+
+        // Parallel to the plane.
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(ray.origin)) / denominator;
+        if t <= min || t >= max {
+            return None;
+        }
+
+        // Express the hit point in terms of the two edges to see if it's inside.
+        let point = ray.point(t);
+        let planar = point - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(Hit::new(
+            t,
+            point,
+            self.normal,
+            (alpha, beta),
+            self.material.as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let corners = [
+            self.q,
+            self.q + self.u,
+            self.q + self.v,
+            self.q + self.u + self.v,
+        ];
+
+        corners
+            .iter()
+            .fold(Aabb::empty(), |bounds, corner| {
+                bounds.surrounding(&Aabb::new(*corner, *corner))
+            })
+            .pad(0.0001)
+    }
+
+    /// Samples a point uniformly over the quad's area.
+    fn sample_direction(
+        &self,
+        origin: Vector3<f64>,
+        sampler: &mut Sampler,
+    ) -> Option<(Vector3<f64>, f64)> {
+        let point = self.q + self.u * sampler.next_f64() + self.v * sampler.next_f64();
+        let to_point = point - origin;
+        let pdf = self.area_to_solid_angle(to_point.magnitude2(), to_point)?;
+
+        Some((to_point.normalize(), pdf))
+    }
+
+    fn direction_pdf(
+        &self,
+        origin: Vector3<f64>,
+        direction: Vector3<f64>,
+        max: f64,
+        sampler: &mut Sampler,
+    ) -> f64 {
+        let ray = Ray::new(origin, direction);
+        let Some(hit) = self.hit(&ray, 0.001, max, sampler) else {
+            return 0.0;
+        };
+
+        let distance2 = hit.t * hit.t * direction.magnitude2();
+        self.area_to_solid_angle(distance2, direction)
+            .unwrap_or(0.0)
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        if self.material.is_emissive() {
+            lights.push(Arc::new(self.clone()));
+        }
+    }
+}
+
+impl Quad {
+    /// Converts the uniform density over the quad's area into one over the solid angle it fills,
+    /// for a point `distance2` away (squared) in `direction`. Seen edge on, there's none.
+    fn area_to_solid_angle(&self, distance2: f64, direction: Vector3<f64>) -> Option<f64> {
+        let cosine = self.normal.dot(direction).abs() / direction.magnitude();
+        if cosine < 1e-8 {
+            return None;
+        }
+
+        Some(distance2 / (cosine * self.area))
+    }
+}
+
+/// An axis-aligned box made of six quads, all facing outwards.
+pub struct Cuboid {
+    sides: HittableList,
+    bounds: Aabb,
+}
+
+impl Cuboid {
+    pub fn new(a: Vector3<f64>, b: Vector3<f64>, material: Arc<dyn Material>) -> Cuboid {
+        let min = Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let max = Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+
+        let dx = Vector3::new(max.x - min.x, 0.0, 0.0);
+        let dy = Vector3::new(0.0, max.y - min.y, 0.0);
+        let dz = Vector3::new(0.0, 0.0, max.z - min.z);
+
+        let mut sides = HittableList::new();
+        // front, right, back, left, top, bottom
+        let faces = [
+            (Vector3::new(min.x, min.y, max.z), dx, dy),
+            (Vector3::new(max.x, min.y, max.z), -dz, dy),
+            (Vector3::new(max.x, min.y, min.z), -dx, dy),
+            (Vector3::new(min.x, min.y, min.z), dz, dy),
+            (Vector3::new(min.x, max.y, max.z), dx, -dz),
+            (Vector3::new(min.x, min.y, min.z), dx, dz),
+        ];
+        for (q, u, v) in faces {
+            sides.push(Quad::new(q, u, v, material.clone()));
+        }
+
+        Cuboid {
+            sides,
+            bounds: Aabb::new(min, max),
+        }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, min: f64, max: f64, sampler: &mut Sampler) -> Option<Hit<'_>> {
+        self.sides.hit(ray, min, max, sampler)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Arc<dyn Hittable>>) {
+        self.sides.collect_lights(lights);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white() -> Arc<dyn Material> {
+        make_lambertian(Vector3::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn quad_hit_reports_normal_and_uv() {
+        let quad = Quad::xy_rect(0.0, 2.0, 0.0, 4.0, -1.0, white());
+        let ray = Ray::new(Vector3::new(0.5, 3.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+
+        let hit = quad
+            .hit(&ray, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+            .unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-12);
+        assert_eq!(hit.normal, Vector3::new(0.0, 0.0, 1.0));
+        assert!((hit.u - 0.25).abs() < 1e-12);
+        assert!((hit.v - 0.75).abs() < 1e-12);
+
+        let outside = Ray::new(Vector3::new(2.5, 3.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        assert!(quad
+            .hit(&outside, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+            .is_none());
+    }
+
+    #[test]
+    fn cuboid_normals_face_outwards() {
+        let cuboid = Cuboid::new(
+            Vector3::new(-1.0, -1.0, -1.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            white(),
+        );
+        let directions = [
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(-1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+        ];
+
+        for direction in directions {
+            // Shoot at each face from outside, slightly off center.
+            let origin = direction * 5.0 + Vector3::new(0.1, 0.2, 0.3);
+            let ray = Ray::new(origin, -direction);
+
+            let hit = cuboid
+                .hit(&ray, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+                .unwrap();
+            assert!((hit.t - 4.0).abs() < 0.31);
+            assert_eq!(hit.normal, direction);
+        }
+    }
+}
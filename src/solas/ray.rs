@@ -1,15 +1,25 @@
+use super::Sampler;
 /// Ray
 use cgmath::{prelude::*, Vector3};
-use random_number::random;
 
 pub struct Ray {
     pub origin: Vector3<f64>,
     pub direction: Vector3<f64>,
+    /// When the ray was sent, for motion blur.
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Vector3<f64>, direction: Vector3<f64>) -> Ray {
-        Ray { origin, direction }
+        Ray::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Vector3<f64>, direction: Vector3<f64>, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn point(&self, t: f64) -> Vector3<f64> {
@@ -17,11 +27,11 @@ impl Ray {
     }
 }
 
-pub fn random_in_unit_sphere() -> Vector3<f64> {
+pub fn random_in_unit_sphere(sampler: &mut Sampler) -> Vector3<f64> {
     loop {
-        let x: f64 = random!();
-        let y: f64 = random!();
-        let z: f64 = random!();
+        let x = sampler.next_f64();
+        let y = sampler.next_f64();
+        let z = sampler.next_f64();
 
         let p = 2.0 * Vector3::new(x, y, z) - Vector3::new(1.0, 1.0, 1.0);
 
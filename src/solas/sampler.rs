@@ -0,0 +1,70 @@
+// Sampler
+
+pub const DEFAULT_SEED: u64 = 0x853c_49e6_748f_ea9b;
+
+/// Seedable source of random numbers (a small PCG32 generator). Everything random in the
+/// renderer draws from one of these, so a render and its scene layout can be reproduced exactly.
+#[derive(Clone)]
+pub struct Sampler {
+    state: u64,
+    increment: u64,
+}
+
+impl Sampler {
+    pub fn new(seed: u64, stream: u64) -> Sampler {
+        let mut rng = Sampler {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(self.increment);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+
+        xorshifted.rotate_right(rotation)
+    }
+
+    /// Uniformly distributed in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        let bits = ((self.next_u32() as u64) << 32) | self.next_u32() as u64;
+
+        (bits >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Sampler::new(42, 7);
+        let mut b = Sampler::new(42, 7);
+
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+
+    #[test]
+    fn f64_in_unit_interval() {
+        let mut rng = Sampler::new(DEFAULT_SEED, 0);
+
+        for _ in 0..10000 {
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+}
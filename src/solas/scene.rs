@@ -0,0 +1,55 @@
+// Scene
+
+use super::*;
+use cgmath::{prelude::*, Vector3};
+use image::Rgb;
+
+/// What a ray sees when it leaves the scene without hitting anything.
+#[derive(Copy, Clone, Debug)]
+pub enum Background {
+    /// The classic white-to-blue sky gradient.
+    Sky,
+    Solid(Vector3<f64>),
+}
+
+impl Background {
+    pub fn color(&self, ray: &Ray) -> Rgb<f64> {
+        match self {
+            Background::Sky => {
+                let unit_direction = ray.direction.normalize();
+                let t = 0.5 * unit_direction.y + 1.0;
+                let lerp =
+                    (1.0 - t) * Vector3::new(1.0, 1.0, 1.0) + t * Vector3::new(0.5, 0.7, 1.0);
+
+                lerp.to_color()
+            }
+            Background::Solid(color) => color.to_color(),
+        }
+    }
+}
+
+/// Everything needed to render an image: where we're looking from and what we're looking at.
+pub struct Scene {
+    pub camera: Camera,
+    pub world: Bvh,
+    pub background: Background,
+    /// Fog filling the whole scene, if any.
+    pub fog: Option<Fog>,
+    /// Emitters in the world that can be sampled directly.
+    pub lights: LightList,
+}
+
+impl Scene {
+    pub fn new(camera: Camera, world: HittableList, background: Background) -> Scene {
+        let world = Bvh::new(world);
+        let lights = LightList::new(&world);
+
+        Scene {
+            camera,
+            world,
+            background,
+            fog: None,
+            lights,
+        }
+    }
+}
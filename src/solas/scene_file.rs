@@ -0,0 +1,1215 @@
+// Scene files
+//
+// Scenes can be described in TOML instead of being hard-coded, e.g.
+//
+//     [render]
+//     width = 600
+//     height = 338
+//     samples = 20
+//     filter = "mitchell"
+//
+//     [camera]
+//     look_from = [13.0, 2.0, 3.0]
+//     look_at = [0.0, 0.0, 0.0]
+//     vfov = 20.0
+//     aperture = 0.1
+//
+//     [materials.ground]
+//     type = "lambertian"
+//     albedo = [0.5, 0.5, 0.5]
+//
+//     [[objects]]
+//     type = "sphere"
+//     center = [0.0, -1000.0, 0.0]
+//     radius = 1000.0
+//     material = "ground"
+
+use super::*;
+use cgmath::{prelude::*, Vector3};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use toml::Spanned;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Invalid { line: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(error) => write!(f, "could not read scene file: {}", error),
+            SceneError::Parse(error) => write!(f, "{}", error),
+            SceneError::Invalid { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// Render settings a scene file may specify; anything left out falls back to the defaults.
+#[derive(Default, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RenderOptions {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub samples: Option<u16>,
+    /// Most bounces a path can take.
+    pub max_depth: Option<u32>,
+    /// Relative error at which pixels stop being sampled.
+    pub threshold: Option<f64>,
+    /// In seconds.
+    pub time_limit: Option<f64>,
+    pub filter: Option<Filter>,
+    pub tone_map: Option<ToneMap>,
+    /// In stops.
+    pub exposure: Option<f64>,
+    pub white_point: Option<f64>,
+}
+
+pub struct SceneDescription {
+    pub scene: Scene,
+    pub render: RenderOptions,
+    /// The camera's aspect ratio, if the scene fixes one. Otherwise it should follow the shape of
+    /// the image being rendered.
+    pub aspect: Option<f64>,
+    /// The meshes and images the scene read, which it depends on as much as its own text.
+    pub files: Vec<PathBuf>,
+}
+
+impl From<Scene> for SceneDescription {
+    fn from(scene: Scene) -> Self {
+        SceneDescription {
+            scene,
+            render: RenderOptions::default(),
+            aspect: None,
+            files: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default)]
+    render: Option<Spanned<RenderOptions>>,
+    camera: Spanned<CameraDescription>,
+    #[serde(default)]
+    background: BackgroundDescription,
+    fog: Option<Spanned<FogDescription>>,
+    #[serde(default)]
+    textures: BTreeMap<String, Spanned<TextureDescription>>,
+    #[serde(default)]
+    materials: BTreeMap<String, Spanned<MaterialDescription>>,
+    /// Named objects that aren't part of the scene themselves, but can be instanced.
+    #[serde(default)]
+    shapes: BTreeMap<String, Spanned<ObjectDescription>>,
+    #[serde(default)]
+    objects: Vec<Spanned<ObjectDescription>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    look_from: [f64; 3],
+    look_at: [f64; 3],
+    #[serde(default = "default_vup")]
+    vup: [f64; 3],
+    vfov: f64,
+    /// Defaults to the render's width / height, or 16:9.
+    aspect: Option<f64>,
+    #[serde(default)]
+    aperture: f64,
+    /// Defaults to the distance between `look_from` and `look_at`.
+    focus_dist: Option<f64>,
+    /// Open and close times; moving objects blur over the interval.
+    #[serde(default)]
+    shutter: [f64; 2],
+}
+
+fn default_vup() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+#[derive(Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundDescription {
+    #[default]
+    Sky,
+    Solid {
+        color: [f64; 3],
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FogDescription {
+    density: f64,
+    #[serde(default = "default_color")]
+    color: [f64; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDescription {
+    Solid {
+        color: [f64; 3],
+    },
+    Checker {
+        scale: f64,
+        even: [f64; 3],
+        odd: [f64; 3],
+    },
+    /// Image file, relative to the scene file.
+    Image {
+        file: String,
+        #[serde(default)]
+        wrap: WrapMode,
+    },
+    Noise {
+        scale: f64,
+        #[serde(default = "default_color")]
+        color: [f64; 3],
+        #[serde(default = "default_seed")]
+        seed: u64,
+    },
+    Marble {
+        scale: f64,
+        #[serde(default = "default_color")]
+        color: [f64; 3],
+        #[serde(default = "default_seed")]
+        seed: u64,
+    },
+    Wood {
+        scale: f64,
+        light: [f64; 3],
+        dark: [f64; 3],
+        #[serde(default = "default_seed")]
+        seed: u64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RotationDescription {
+    axis: [f64; 3],
+    degrees: f64,
+}
+
+fn default_scale() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_time1() -> f64 {
+    1.0
+}
+
+fn default_color() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_seed() -> u64 {
+    DEFAULT_SEED
+}
+
+/// A material's color: either a constant or the name of a texture.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AlbedoDescription {
+    Color([f64; 3]),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDescription {
+    Lambertian {
+        albedo: AlbedoDescription,
+    },
+    Metal {
+        albedo: AlbedoDescription,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    #[serde(alias = "dialectric")]
+    Dielectric {
+        refractive_index: f64,
+    },
+    /// A rough metal, given either as a `metal` preset or by its complex refractive index.
+    Conductor {
+        metal: Option<Metal>,
+        eta: Option<[f64; 3]>,
+        k: Option<[f64; 3]>,
+        #[serde(default)]
+        roughness: f64,
+    },
+    RoughDielectric {
+        refractive_index: f64,
+        #[serde(default)]
+        roughness: f64,
+    },
+    /// One material for most surfaces; everything besides the base color is optional.
+    Principled {
+        base_color: AlbedoDescription,
+        #[serde(flatten)]
+        parameters: PrincipledDescription,
+    },
+    DiffuseLight {
+        emit: [f64; 3],
+    },
+    /// Scatters equally in every direction; for volumes.
+    Isotropic {
+        albedo: AlbedoDescription,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrincipledDescription {
+    metallic: Option<f64>,
+    roughness: Option<f64>,
+    specular: Option<f64>,
+    clearcoat: Option<f64>,
+    clearcoat_roughness: Option<f64>,
+    sheen: Option<f64>,
+    transmission: Option<f64>,
+    subsurface: Option<f64>,
+    refractive_index: Option<f64>,
+}
+
+/// The closed shape a volume fills.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BoundaryDescription {
+    Sphere { center: [f64; 3], radius: f64 },
+    Box { min: [f64; 3], max: [f64; 3] },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    /// Sphere moving from `center0` at `time0` to `center1` at `time1`.
+    MovingSphere {
+        center0: [f64; 3],
+        center1: [f64; 3],
+        #[serde(default)]
+        time0: f64,
+        #[serde(default = "default_time1")]
+        time1: f64,
+        radius: f64,
+        material: String,
+    },
+    /// Constant-density smoke or fog filling `boundary`; `material` is usually isotropic.
+    Medium {
+        boundary: BoundaryDescription,
+        density: f64,
+        material: String,
+    },
+    /// A copy of one of the named shapes, scaled, then rotated, then translated.
+    Instance {
+        shape: String,
+        #[serde(default = "default_scale")]
+        scale: [f64; 3],
+        rotate: Option<RotationDescription>,
+        #[serde(default)]
+        translate: [f64; 3],
+    },
+    /// Parallelogram with corner `q` and edges `u` and `v`.
+    Quad {
+        q: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    /// Axis-aligned box between two opposite corners.
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: String,
+    },
+    /// Counter-clockwise when seen from the front.
+    Triangle {
+        a: [f64; 3],
+        b: [f64; 3],
+        c: [f64; 3],
+        material: String,
+    },
+    /// Wavefront OBJ file, relative to the scene file. `material` is used for faces without a
+    /// `usemtl`; `materials` maps the OBJ's material names onto the scene's, and names it
+    /// doesn't mention are looked up among the scene's materials as they are. A name found in
+    /// neither is an error.
+    Mesh {
+        file: String,
+        material: String,
+        #[serde(default)]
+        materials: BTreeMap<String, String>,
+    },
+}
+
+fn vector(v: [f64; 3]) -> Vector3<f64> {
+    Vector3::new(v[0], v[1], v[2])
+}
+
+/// Reports problems in terms of the line the offending table starts on.
+struct Validator<'a> {
+    text: &'a str,
+    /// Where files the scene refers to are looked for.
+    directory: &'a Path,
+    /// The files read so far.
+    files: RefCell<Vec<PathBuf>>,
+}
+
+impl Validator<'_> {
+    fn line<T>(&self, item: &Spanned<T>) -> usize {
+        self.text[..item.span().start].matches('\n').count() + 1
+    }
+
+    fn check<T>(
+        &self,
+        item: &Spanned<T>,
+        ok: bool,
+        message: impl Fn() -> String,
+    ) -> Result<(), SceneError> {
+        if ok {
+            Ok(())
+        } else {
+            Err(SceneError::Invalid {
+                line: self.line(item),
+                message: message(),
+            })
+        }
+    }
+
+    fn camera(
+        &self,
+        description: &Spanned<CameraDescription>,
+        default_aspect: f64,
+    ) -> Result<Camera, SceneError> {
+        let camera = description.get_ref();
+        let look_from = vector(camera.look_from);
+        let look_at = vector(camera.look_at);
+        let vup = vector(camera.vup);
+        let aspect = camera.aspect.unwrap_or(default_aspect);
+        let focus_dist = camera
+            .focus_dist
+            .unwrap_or_else(|| (look_at - look_from).magnitude());
+
+        self.check(description, look_from != look_at, || {
+            "camera.look_from and camera.look_at must differ".to_string()
+        })?;
+        self.check(
+            description,
+            vup.cross(look_from - look_at).magnitude() > 0.0,
+            || "camera.vup must not be parallel to the view direction".to_string(),
+        )?;
+        self.check(
+            description,
+            camera.vfov > 0.0 && camera.vfov < 180.0,
+            || format!("camera.vfov must be between 0 and 180, got {}", camera.vfov),
+        )?;
+        self.check(description, aspect > 0.0, || {
+            format!("camera.aspect must be positive, got {}", aspect)
+        })?;
+        self.check(description, camera.aperture >= 0.0, || {
+            format!(
+                "camera.aperture must not be negative, got {}",
+                camera.aperture
+            )
+        })?;
+        self.check(description, focus_dist > 0.0, || {
+            format!("camera.focus_dist must be positive, got {}", focus_dist)
+        })?;
+        self.check(description, camera.shutter[0] <= camera.shutter[1], || {
+            "camera.shutter must open before it closes".to_string()
+        })?;
+
+        Ok(Camera::new(
+            look_from,
+            look_at,
+            vup,
+            camera.vfov,
+            aspect,
+            camera.aperture,
+            focus_dist,
+            camera.shutter[0],
+            camera.shutter[1],
+        ))
+    }
+
+    fn texture(
+        &self,
+        name: &str,
+        description: &Spanned<TextureDescription>,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        let color_ok = |color: &[f64; 3]| color.iter().all(|c| *c >= 0.0);
+
+        match description.get_ref() {
+            TextureDescription::Solid { color } => {
+                self.check(description, color_ok(color), || {
+                    format!("textures.{}.color must not be negative", name)
+                })?;
+
+                Ok(Arc::new(SolidColor::new(vector(*color))))
+            }
+            TextureDescription::Checker { scale, even, odd } => {
+                self.check(description, *scale > 0.0, || {
+                    format!("textures.{}.scale must be positive, got {}", name, scale)
+                })?;
+                self.check(description, color_ok(even) && color_ok(odd), || {
+                    format!("textures.{}: colors must not be negative", name)
+                })?;
+
+                Ok(Arc::new(Checker::new(
+                    *scale,
+                    Arc::new(SolidColor::new(vector(*even))),
+                    Arc::new(SolidColor::new(vector(*odd))),
+                )))
+            }
+            TextureDescription::Image { file, wrap } => {
+                let path = self.directory.join(file);
+                let image =
+                    ImageTexture::load(&path, *wrap).map_err(|error| SceneError::Invalid {
+                        line: self.line(description),
+                        message: format!("textures.{}: {}: {}", name, path.display(), error),
+                    })?;
+                self.files.borrow_mut().push(path);
+
+                Ok(Arc::new(image))
+            }
+            TextureDescription::Noise { scale, color, seed } => {
+                self.check(description, *scale > 0.0, || {
+                    format!("textures.{}.scale must be positive, got {}", name, scale)
+                })?;
+                self.check(description, color_ok(color), || {
+                    format!("textures.{}.color must not be negative", name)
+                })?;
+
+                Ok(Arc::new(NoiseTexture::new(*seed, *scale, vector(*color))))
+            }
+            TextureDescription::Marble { scale, color, seed } => {
+                self.check(description, *scale > 0.0, || {
+                    format!("textures.{}.scale must be positive, got {}", name, scale)
+                })?;
+                self.check(description, color_ok(color), || {
+                    format!("textures.{}.color must not be negative", name)
+                })?;
+
+                Ok(Arc::new(MarbleTexture::new(*seed, *scale, vector(*color))))
+            }
+            TextureDescription::Wood {
+                scale,
+                light,
+                dark,
+                seed,
+            } => {
+                self.check(description, *scale > 0.0, || {
+                    format!("textures.{}.scale must be positive, got {}", name, scale)
+                })?;
+                self.check(description, color_ok(light) && color_ok(dark), || {
+                    format!("textures.{}: colors must not be negative", name)
+                })?;
+
+                Ok(Arc::new(WoodTexture::new(
+                    *seed,
+                    *scale,
+                    vector(*light),
+                    vector(*dark),
+                )))
+            }
+        }
+    }
+
+    fn albedo(
+        &self,
+        name: &str,
+        description: &Spanned<MaterialDescription>,
+        albedo: &AlbedoDescription,
+        textures: &BTreeMap<&str, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        match albedo {
+            AlbedoDescription::Color(color) => {
+                self.check(description, color.iter().all(|c| *c >= 0.0), || {
+                    format!("materials.{}.albedo must not be negative", name)
+                })?;
+
+                Ok(Arc::new(SolidColor::new(vector(*color))))
+            }
+            AlbedoDescription::Texture(texture) => textures
+                .get(texture.as_str())
+                .cloned()
+                .ok_or_else(|| SceneError::Invalid {
+                    line: self.line(description),
+                    message: format!("materials.{}.albedo: unknown texture \"{}\"", name, texture),
+                }),
+        }
+    }
+
+    /// Checks a material parameter that runs from 0 to 1.
+    fn fraction(
+        &self,
+        name: &str,
+        description: &Spanned<MaterialDescription>,
+        field: &str,
+        value: f64,
+    ) -> Result<(), SceneError> {
+        self.check(description, (0.0..=1.0).contains(&value), || {
+            format!(
+                "materials.{}.{} must be between 0 and 1, got {}",
+                name, field, value
+            )
+        })
+    }
+
+    fn material(
+        &self,
+        name: &str,
+        description: &Spanned<MaterialDescription>,
+        textures: &BTreeMap<&str, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        let albedo_ok = |albedo: &[f64; 3]| albedo.iter().all(|c| *c >= 0.0);
+
+        match description.get_ref() {
+            MaterialDescription::Lambertian { albedo } => {
+                let albedo = self.albedo(name, description, albedo, textures)?;
+
+                Ok(make_textured_lambertian(albedo))
+            }
+            MaterialDescription::Metal { albedo, fuzz } => {
+                let albedo = self.albedo(name, description, albedo, textures)?;
+                self.check(description, (0.0..=1.0).contains(fuzz), || {
+                    format!(
+                        "materials.{}.fuzz must be between 0 and 1, got {}",
+                        name, fuzz
+                    )
+                })?;
+
+                Ok(make_textured_metal(albedo, *fuzz))
+            }
+            MaterialDescription::Dielectric { refractive_index } => {
+                self.check(description, *refractive_index > 0.0, || {
+                    format!(
+                        "materials.{}.refractive_index must be positive, got {}",
+                        name, refractive_index
+                    )
+                })?;
+
+                Ok(make_dialectric(*refractive_index))
+            }
+            MaterialDescription::Conductor {
+                metal,
+                eta,
+                k,
+                roughness,
+            } => {
+                self.fraction(name, description, "roughness", *roughness)?;
+
+                match (metal, eta, k) {
+                    (Some(metal), None, None) => Ok(make_conductor(*metal, *roughness)),
+                    (None, Some(eta), Some(k)) => {
+                        self.check(description, eta.iter().all(|c| *c > 0.0), || {
+                            format!("materials.{}.eta must be positive", name)
+                        })?;
+                        self.check(description, albedo_ok(k), || {
+                            format!("materials.{}.k must not be negative", name)
+                        })?;
+
+                        Ok(Arc::new(ConductorMaterial::new(
+                            vector(*eta),
+                            vector(*k),
+                            *roughness,
+                        )))
+                    }
+                    _ => Err(SceneError::Invalid {
+                        line: self.line(description),
+                        message: format!(
+                            "materials.{} needs either a metal or both eta and k",
+                            name
+                        ),
+                    }),
+                }
+            }
+            MaterialDescription::RoughDielectric {
+                refractive_index,
+                roughness,
+            } => {
+                self.check(description, *refractive_index > 0.0, || {
+                    format!(
+                        "materials.{}.refractive_index must be positive, got {}",
+                        name, refractive_index
+                    )
+                })?;
+                self.fraction(name, description, "roughness", *roughness)?;
+
+                Ok(make_rough_dielectric(*refractive_index, *roughness))
+            }
+            MaterialDescription::Principled {
+                base_color,
+                parameters,
+            } => {
+                let defaults = Principled::default();
+                let principled = Principled {
+                    metallic: parameters.metallic.unwrap_or(defaults.metallic),
+                    roughness: parameters.roughness.unwrap_or(defaults.roughness),
+                    specular: parameters.specular.unwrap_or(defaults.specular),
+                    clearcoat: parameters.clearcoat.unwrap_or(defaults.clearcoat),
+                    clearcoat_roughness: parameters
+                        .clearcoat_roughness
+                        .unwrap_or(defaults.clearcoat_roughness),
+                    sheen: parameters.sheen.unwrap_or(defaults.sheen),
+                    transmission: parameters.transmission.unwrap_or(defaults.transmission),
+                    subsurface: parameters.subsurface.unwrap_or(defaults.subsurface),
+                    refractive_index: parameters
+                        .refractive_index
+                        .unwrap_or(defaults.refractive_index),
+                };
+
+                for (field, value) in [
+                    ("metallic", principled.metallic),
+                    ("roughness", principled.roughness),
+                    ("specular", principled.specular),
+                    ("clearcoat", principled.clearcoat),
+                    ("clearcoat_roughness", principled.clearcoat_roughness),
+                    ("sheen", principled.sheen),
+                    ("transmission", principled.transmission),
+                    ("subsurface", principled.subsurface),
+                ] {
+                    self.fraction(name, description, field, value)?;
+                }
+                self.check(description, principled.refractive_index > 0.0, || {
+                    format!(
+                        "materials.{}.refractive_index must be positive, got {}",
+                        name, principled.refractive_index
+                    )
+                })?;
+
+                match base_color {
+                    AlbedoDescription::Color(color) => {
+                        self.check(description, albedo_ok(color), || {
+                            format!("materials.{}.base_color must not be negative", name)
+                        })?;
+
+                        Ok(make_principled(vector(*color), principled))
+                    }
+                    texture => {
+                        let base_color = self.albedo(name, description, texture, textures)?;
+
+                        Ok(make_textured_principled(base_color, principled))
+                    }
+                }
+            }
+            MaterialDescription::DiffuseLight { emit } => {
+                self.check(description, albedo_ok(emit), || {
+                    format!("materials.{}.emit must not be negative", name)
+                })?;
+
+                Ok(make_diffuse_light(vector(*emit)))
+            }
+            MaterialDescription::Isotropic { albedo } => {
+                let albedo = self.albedo(name, description, albedo, textures)?;
+
+                Ok(Arc::new(IsotropicMaterial::new(albedo)))
+            }
+        }
+    }
+
+    fn object(
+        &self,
+        label: &str,
+        description: &Spanned<ObjectDescription>,
+        materials: &BTreeMap<&str, Arc<dyn Material>>,
+        shapes: &BTreeMap<&str, Arc<dyn Hittable>>,
+        world: &mut HittableList,
+    ) -> Result<(), SceneError> {
+        let lookup = |name: &str| {
+            materials
+                .get(name)
+                .cloned()
+                .ok_or_else(|| SceneError::Invalid {
+                    line: self.line(description),
+                    message: format!("{}.material: unknown material \"{}\"", label, name),
+                })
+        };
+
+        match description.get_ref() {
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                self.check(description, *radius > 0.0, || {
+                    format!("{}.radius must be positive, got {}", label, radius)
+                })?;
+
+                world.push(Sphere::new(vector(*center), *radius, lookup(material)?));
+            }
+            ObjectDescription::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => {
+                self.check(description, *radius > 0.0, || {
+                    format!("{}.radius must be positive, got {}", label, radius)
+                })?;
+                self.check(description, time0 < time1, || {
+                    format!("{}: time0 must be before time1", label)
+                })?;
+
+                world.push(MovingSphere::new(
+                    (vector(*center0), *time0),
+                    (vector(*center1), *time1),
+                    *radius,
+                    lookup(material)?,
+                ));
+            }
+            ObjectDescription::Medium {
+                boundary,
+                density,
+                material,
+            } => {
+                self.check(description, *density > 0.0, || {
+                    format!("{}.density must be positive, got {}", label, density)
+                })?;
+
+                let material = lookup(material)?;
+                match boundary {
+                    BoundaryDescription::Sphere { center, radius } => {
+                        self.check(description, *radius > 0.0, || {
+                            format!("{}.boundary.radius must be positive, got {}", label, radius)
+                        })?;
+
+                        let sphere = Sphere::new(vector(*center), *radius, material.clone());
+                        world.push(ConstantMedium::new(sphere, *density, material));
+                    }
+                    BoundaryDescription::Box { min, max } => {
+                        self.check(
+                            description,
+                            (0..3).all(|axis| min[axis] < max[axis]),
+                            || format!("{}.boundary: min must be below max on every axis", label),
+                        )?;
+
+                        let cuboid = Cuboid::new(vector(*min), vector(*max), material.clone());
+                        world.push(ConstantMedium::new(cuboid, *density, material));
+                    }
+                }
+            }
+            ObjectDescription::Instance {
+                shape,
+                scale,
+                rotate,
+                translate,
+            } => {
+                let object = shapes
+                    .get(shape.as_str())
+                    .ok_or_else(|| SceneError::Invalid {
+                        line: self.line(description),
+                        message: format!("{}.shape: unknown shape \"{}\"", label, shape),
+                    })?;
+                self.check(description, scale.iter().all(|s| *s != 0.0), || {
+                    format!("{}.scale must not be zero", label)
+                })?;
+
+                let mut instance = Instance::of(object.clone()).scale(vector(*scale));
+                if let Some(rotation) = rotate {
+                    self.check(description, vector(rotation.axis).magnitude() > 0.0, || {
+                        format!("{}.rotate.axis must not be zero", label)
+                    })?;
+
+                    instance = instance.rotate(vector(rotation.axis), rotation.degrees);
+                }
+                world.push(instance.translate(vector(*translate)));
+            }
+            ObjectDescription::Quad { q, u, v, material } => {
+                self.check(
+                    description,
+                    vector(*u).cross(vector(*v)).magnitude() > 0.0,
+                    || format!("{}: u and v must not be parallel", label),
+                )?;
+
+                world.push(Quad::new(
+                    vector(*q),
+                    vector(*u),
+                    vector(*v),
+                    lookup(material)?,
+                ));
+            }
+            ObjectDescription::Box { min, max, material } => {
+                self.check(
+                    description,
+                    (0..3).all(|axis| min[axis] < max[axis]),
+                    || format!("{}: min must be below max on every axis", label),
+                )?;
+
+                world.push(Cuboid::new(vector(*min), vector(*max), lookup(material)?));
+            }
+            ObjectDescription::Triangle { a, b, c, material } => {
+                let (a, b, c) = (vector(*a), vector(*b), vector(*c));
+                self.check(description, (b - a).cross(c - a).magnitude() > 0.0, || {
+                    format!("{}: a, b and c must not be in a line", label)
+                })?;
+
+                world.push(Triangle::new(a, b, c, lookup(material)?));
+            }
+            ObjectDescription::Mesh {
+                file,
+                material,
+                materials: renamed,
+            } => {
+                let mut mesh_materials = materials.clone();
+                for (name, material) in renamed {
+                    mesh_materials.insert(name.as_str(), lookup(material)?);
+                }
+
+                let path = self.directory.join(file);
+                let mesh =
+                    load_obj(&path, lookup(material)?, &mesh_materials).map_err(|error| {
+                        SceneError::Invalid {
+                            line: self.line(description),
+                            message: format!("{}: {}: {}", label, path.display(), error),
+                        }
+                    })?;
+                self.check(description, !mesh.faces.is_empty(), || {
+                    format!("{}: {} has no faces", label, path.display())
+                })?;
+                self.files.borrow_mut().push(path);
+
+                for triangle in mesh.into_triangles() {
+                    world.push(triangle);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn load_scene(path: &Path) -> Result<SceneDescription, SceneError> {
+    let text = fs::read_to_string(path).map_err(SceneError::Io)?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    parse(&text, directory)
+}
+
+/// Parses a scene whose files, if any, are relative to the working directory.
+pub fn parse_scene(text: &str) -> Result<SceneDescription, SceneError> {
+    parse(text, Path::new(""))
+}
+
+fn parse(text: &str, directory: &Path) -> Result<SceneDescription, SceneError> {
+    let file: SceneFile = toml::from_str(text).map_err(SceneError::Parse)?;
+    let validator = Validator {
+        text,
+        directory,
+        files: RefCell::new(vec![]),
+    };
+
+    let render = match file.render {
+        Some(render) => {
+            let options = render.get_ref();
+            validator.check(&render, options.width != Some(0), || {
+                "render.width must be positive".to_string()
+            })?;
+            validator.check(&render, options.height != Some(0), || {
+                "render.height must be positive".to_string()
+            })?;
+            validator.check(&render, options.samples != Some(0), || {
+                "render.samples must be positive".to_string()
+            })?;
+            validator.check(
+                &render,
+                options.threshold.is_none_or(|threshold| threshold > 0.0),
+                || "render.threshold must be positive".to_string(),
+            )?;
+            validator.check(
+                &render,
+                options.time_limit.is_none_or(|seconds| seconds > 0.0),
+                || "render.time_limit must be positive".to_string(),
+            )?;
+            validator.check(
+                &render,
+                options.white_point.is_none_or(|white| white > 0.0),
+                || "render.white_point must be positive".to_string(),
+            )?;
+
+            render.into_inner()
+        }
+        None => RenderOptions::default(),
+    };
+
+    let default_aspect = match (render.width, render.height) {
+        (Some(width), Some(height)) => width as f64 / height as f64,
+        _ => 16.0 / 9.0,
+    };
+    let camera = validator.camera(&file.camera, default_aspect)?;
+
+    let background = match file.background {
+        BackgroundDescription::Sky => Background::Sky,
+        BackgroundDescription::Solid { color } => Background::Solid(vector(color)),
+    };
+
+    let fog = match &file.fog {
+        Some(description) => {
+            let fog = description.get_ref();
+            validator.check(description, fog.density > 0.0, || {
+                format!("fog.density must be positive, got {}", fog.density)
+            })?;
+            validator.check(description, fog.color.iter().all(|c| *c >= 0.0), || {
+                "fog.color must not be negative".to_string()
+            })?;
+
+            Some(Fog::new(fog.density, vector(fog.color)))
+        }
+        None => None,
+    };
+
+    let mut textures = BTreeMap::new();
+    for (name, description) in &file.textures {
+        textures.insert(name.as_str(), validator.texture(name, description)?);
+    }
+
+    let mut materials = BTreeMap::new();
+    for (name, description) in &file.materials {
+        materials.insert(
+            name.as_str(),
+            validator.material(name, description, &textures)?,
+        );
+    }
+
+    // Each shape gets its own BVH, shared by all its instances.
+    let mut shapes: BTreeMap<&str, Arc<dyn Hittable>> = BTreeMap::new();
+    for (name, description) in &file.shapes {
+        let label = format!("shapes.{}", name);
+        validator.check(
+            description,
+            !matches!(description.get_ref(), ObjectDescription::Instance { .. }),
+            || format!("{}: shapes can't be instances", label),
+        )?;
+
+        let mut parts = HittableList::new();
+        validator.object(&label, description, &materials, &shapes, &mut parts)?;
+        shapes.insert(name.as_str(), Arc::new(Bvh::new(parts)));
+    }
+
+    let mut world = HittableList::new();
+    for (index, description) in file.objects.iter().enumerate() {
+        let label = format!("objects[{}]", index);
+        validator.object(&label, description, &materials, &shapes, &mut world)?;
+    }
+
+    let mut scene = Scene::new(camera, world, background);
+    scene.fog = fog;
+
+    Ok(SceneDescription {
+        scene,
+        render,
+        aspect: file.camera.get_ref().aspect,
+        files: validator.files.into_inner(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_SPHERES: &str = r#"
+[render]
+width = 160
+height = 90
+
+[camera]
+look_from = [13.0, 2.0, 3.0]
+look_at = [0.0, 0.0, 0.0]
+vfov = 20.0
+
+[background]
+type = "solid"
+color = [0.1, 0.1, 0.1]
+
+[materials.ground]
+type = "lambertian"
+albedo = [0.8, 0.8, 0.0]
+
+[materials.glass]
+type = "dielectric"
+refractive_index = 1.5
+
+[[objects]]
+type = "sphere"
+center = [0.0, -100.5, 0.0]
+radius = 100.0
+material = "ground"
+
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 0.5
+material = "glass"
+"#;
+
+    fn error(text: &str) -> String {
+        match parse_scene(text) {
+            Ok(_) => panic!("expected the scene to be rejected"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_scene() {
+        let description = parse_scene(TWO_SPHERES).unwrap();
+
+        assert_eq!(description.render.width, Some(160));
+        assert_eq!(description.render.samples, None);
+        assert_eq!(description.render.max_depth, None);
+
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = description
+            .scene
+            .world
+            .hit(&ray, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+            .unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn render_options_set_max_depth() {
+        let text = TWO_SPHERES.replace("height = 90", "height = 90\nmax_depth = 20");
+        let description = parse_scene(&text).unwrap();
+
+        assert_eq!(description.render.max_depth, Some(20));
+    }
+
+    #[test]
+    fn camera_aspect_is_only_fixed_when_given() {
+        assert_eq!(parse_scene(TWO_SPHERES).unwrap().aspect, None);
+
+        let text = TWO_SPHERES.replace("vfov = 20.0", "vfov = 20.0\naspect = 2.0");
+        assert_eq!(parse_scene(&text).unwrap().aspect, Some(2.0));
+    }
+
+    #[test]
+    fn unknown_material_reports_line() {
+        let text = TWO_SPHERES.replace("material = \"glass\"", "material = \"steel\"");
+
+        let message = error(&text);
+        assert!(message.starts_with("line 29:"), "{}", message);
+        assert!(
+            message.contains("unknown material \"steel\""),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn albedo_can_name_a_texture() {
+        let text = TWO_SPHERES.replace(
+            "albedo = [0.8, 0.8, 0.0]",
+            "albedo = \"checker\"\n\n[textures.checker]\ntype = \"checker\"\nscale = 1.0\neven = [0.2, 0.3, 0.1]\nodd = [0.9, 0.9, 0.9]",
+        );
+        assert!(parse_scene(&text).is_ok());
+
+        let message = error(&text.replace("albedo = \"checker\"", "albedo = \"tiles\""));
+        assert!(message.contains("unknown texture \"tiles\""), "{}", message);
+    }
+
+    #[test]
+    fn conductors_take_a_metal_or_its_refractive_index() {
+        let gold = "type = \"conductor\"\nmetal = \"gold\"\nroughness = 0.2";
+        let text = TWO_SPHERES.replace("type = \"lambertian\"\nalbedo = [0.8, 0.8, 0.0]", gold);
+        assert!(parse_scene(&text).is_ok());
+
+        let custom = "metal = \"gold\"";
+        let index = "eta = [0.2, 0.9, 1.1]\nk = [3.9, 2.5, 2.1]";
+        assert!(parse_scene(&text.replace(custom, index)).is_ok());
+
+        let message = error(&text.replace(custom, "eta = [0.2, 0.9, 1.1]"));
+        assert!(
+            message.contains("materials.ground needs either a metal or both eta and k"),
+            "{}",
+            message
+        );
+
+        let message = error(&text.replace("roughness = 0.2", "roughness = 1.5"));
+        assert!(
+            message.contains("materials.ground.roughness must be between 0 and 1"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn principled_parameters_are_optional() {
+        let plastic = "type = \"principled\"\nbase_color = [0.8, 0.1, 0.1]";
+        let text = TWO_SPHERES.replace("type = \"lambertian\"\nalbedo = [0.8, 0.8, 0.0]", plastic);
+        assert!(parse_scene(&text).is_ok());
+
+        let coated = format!("{}\nmetallic = 0.3\nclearcoat = 1.0\nsheen = 0.5", plastic);
+        assert!(parse_scene(&text.replace(plastic, &coated)).is_ok());
+
+        let message = error(&text.replace(plastic, &format!("{}\ntransmission = 2.0", plastic)));
+        assert!(
+            message.contains("materials.ground.transmission must be between 0 and 1"),
+            "{}",
+            message
+        );
+        let message = error(&text.replace(plastic, &format!("{}\nshininess = 1.0", plastic)));
+        assert!(message.contains("shininess"), "{}", message);
+    }
+
+    #[test]
+    fn instances_place_shapes() {
+        let text = TWO_SPHERES.replace(
+            "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 0.5",
+            "[shapes.ball]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 0.5",
+        ) + "\n[[objects]]\ntype = \"instance\"\nshape = \"ball\"\ntranslate = [0.0, 0.0, -1.0]\n";
+        let description = parse_scene(&text).unwrap();
+
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = description
+            .scene
+            .world
+            .hit(&ray, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+            .unwrap();
+        assert!((hit.t - 5.5).abs() < 1e-9);
+
+        let message = error(&text.replace("shape = \"ball\"", "shape = \"cube\""));
+        assert!(
+            message.contains("objects[1].shape: unknown shape \"cube\""),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn invalid_field_reports_field() {
+        let text = TWO_SPHERES.replace("radius = 0.5", "radius = -0.5");
+
+        let message = error(&text);
+        assert!(
+            message.contains("objects[1].radius must be positive"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn syntax_errors_report_position() {
+        let text = TWO_SPHERES.replace("vfov = 20.0", "vfov = ");
+
+        let message = error(&text);
+        assert!(message.contains("line 9"), "{}", message);
+    }
+}
@@ -0,0 +1,290 @@
+// Textures
+
+use super::{Perlin, RgbExt};
+use cgmath::{prelude::*, Vector3};
+use image::{Rgb, RgbImage};
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A color that varies over a surface, looked up by the hit's surface coordinates `(u, v)` and
+/// its point `p` in space.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: Vector3<f64>) -> Vector3<f64>;
+}
+
+/// The same color everywhere.
+#[derive(Copy, Clone)]
+pub struct SolidColor {
+    color: Vector3<f64>,
+}
+
+impl SolidColor {
+    pub fn new(color: Vector3<f64>) -> Self {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Vector3<f64>) -> Vector3<f64> {
+        self.color
+    }
+}
+
+/// A 3D checkerboard of cubes `scale` wide, alternating between two textures. Being solid,
+/// it doesn't depend on how an object's surface coordinates are laid out.
+pub struct Checker {
+    scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl Checker {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Checker { scale, even, odd }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: Vector3<f64>) -> Vector3<f64> {
+        let cell =
+            (p.x / self.scale).floor() + (p.y / self.scale).floor() + (p.z / self.scale).floor();
+
+        if cell.rem_euclid(2.0) == 0.0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// Perlin noise, shading `color` from black where the noise is lowest to full where it's
+/// highest. `scale` sets how many noise features fit in a unit of distance.
+pub struct NoiseTexture {
+    perlin: Perlin,
+    scale: f64,
+    color: Vector3<f64>,
+}
+
+impl NoiseTexture {
+    pub fn new(seed: u64, scale: f64, color: Vector3<f64>) -> Self {
+        NoiseTexture {
+            perlin: Perlin::new(seed),
+            scale,
+            color,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Vector3<f64>) -> Vector3<f64> {
+        self.color * 0.5 * (1.0 + self.perlin.noise(p * self.scale))
+    }
+}
+
+/// Bands of `color` along the z axis, distorted by turbulence into marble-like veins.
+pub struct MarbleTexture {
+    perlin: Perlin,
+    scale: f64,
+    color: Vector3<f64>,
+}
+
+impl MarbleTexture {
+    pub fn new(seed: u64, scale: f64, color: Vector3<f64>) -> Self {
+        MarbleTexture {
+            perlin: Perlin::new(seed),
+            scale,
+            color,
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: Vector3<f64>) -> Vector3<f64> {
+        let phase = self.scale * p.z + 10.0 * self.perlin.turbulence(p, 7);
+
+        self.color * 0.5 * (1.0 + phase.sin())
+    }
+}
+
+/// Growth rings around the y axis, `scale` to a unit of distance, blending from `light` to
+/// `dark` across each ring. Noise keeps the rings from being perfect circles.
+pub struct WoodTexture {
+    perlin: Perlin,
+    scale: f64,
+    light: Vector3<f64>,
+    dark: Vector3<f64>,
+}
+
+impl WoodTexture {
+    pub fn new(seed: u64, scale: f64, light: Vector3<f64>, dark: Vector3<f64>) -> Self {
+        WoodTexture {
+            perlin: Perlin::new(seed),
+            scale,
+            light,
+            dark,
+        }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: Vector3<f64>) -> Vector3<f64> {
+        let radius = Vector3::new(p.x, 0.0, p.z).magnitude();
+        let rings = self.scale * radius + 0.5 * self.perlin.turbulence(p * 4.0, 3);
+        let t = rings - rings.floor();
+
+        self.light.lerp(self.dark, t * t)
+    }
+}
+
+/// What an image texture does with coordinates outside [0, 1].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapMode {
+    /// Tile the image.
+    #[default]
+    Repeat,
+    /// Tile the image, flipping every other copy so the edges line up.
+    Mirror,
+    /// Stretch the edge pixels outwards.
+    Clamp,
+}
+
+impl WrapMode {
+    fn wrap(&self, index: i64, size: u32) -> u32 {
+        let size = size as i64;
+
+        let wrapped = match self {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::Mirror => {
+                let period = index.rem_euclid(2 * size);
+                if period < size {
+                    period
+                } else {
+                    2 * size - 1 - period
+                }
+            }
+            WrapMode::Clamp => index.clamp(0, size - 1),
+        };
+
+        wrapped as u32
+    }
+}
+
+/// An image mapped onto the surface, with (0, 0) at its bottom left and (1, 1) at its top
+/// right. Lookups are bilinearly filtered between the four nearest pixels.
+pub struct ImageTexture {
+    image: RgbImage,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(image: RgbImage, wrap: WrapMode) -> Self {
+        ImageTexture { image, wrap }
+    }
+
+    /// Loads a PNG, JPEG or any other format the `image` crate can read.
+    pub fn load(path: &Path, wrap: WrapMode) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.to_rgb8();
+
+        Ok(ImageTexture::new(image, wrap))
+    }
+
+    /// Linear pixel color; image files are sRGB encoded.
+    fn pixel(&self, x: i64, y: i64) -> Vector3<f64> {
+        let x = self.wrap.wrap(x, self.image.width());
+        let y = self.wrap.wrap(y, self.image.height());
+        let pixel = self.image.get_pixel(x, y);
+        let channel = |c: u8| c as f64 / 255.0;
+        let linear =
+            Rgb([channel(pixel[0]), channel(pixel[1]), channel(pixel[2])]).srgb_to_linear();
+
+        Vector3::new(linear[0], linear[1], linear[2])
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Vector3<f64>) -> Vector3<f64> {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Vector3::new(0.0, 1.0, 1.0);
+        }
+
+        // Pixel centers sit at half-integer positions; images are stored top row first.
+        let x = u * self.image.width() as f64 - 0.5;
+        let y = (1.0 - v) * self.image.height() as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.pixel(x0, y0) * (1.0 - fx) + self.pixel(x0 + 1, y0) * fx;
+        let bottom = self.pixel(x0, y0 + 1) * (1.0 - fx) + self.pixel(x0 + 1, y0 + 1) * fx;
+
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin() -> Vector3<f64> {
+        Vector3::new(0.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn checker_alternates() {
+        let black = Arc::new(SolidColor::new(Vector3::new(0.0, 0.0, 0.0)));
+        let white = Arc::new(SolidColor::new(Vector3::new(1.0, 1.0, 1.0)));
+        let checker = Checker::new(2.0, white, black);
+
+        let at = |x: f64| checker.value(0.0, 0.0, Vector3::new(x, 0.5, 0.5)).x;
+        assert_eq!(at(0.5), 1.0);
+        assert_eq!(at(2.5), 0.0);
+        assert_eq!(at(-0.5), 0.0);
+        assert_eq!(at(-2.5), 1.0);
+    }
+
+    #[test]
+    fn procedural_textures_stay_in_range() {
+        let color = Vector3::new(0.8, 0.6, 0.4);
+        let textures: [Box<dyn Texture>; 3] = [
+            Box::new(NoiseTexture::new(1, 4.0, color)),
+            Box::new(MarbleTexture::new(1, 4.0, color)),
+            Box::new(WoodTexture::new(1, 4.0, color, Vector3::new(0.0, 0.0, 0.0))),
+        ];
+
+        for texture in &textures {
+            for i in 0..100 {
+                let p = Vector3::new(i as f64 * 0.37, i as f64 * -0.11, i as f64 * 0.53);
+                let value = texture.value(0.0, 0.0, p);
+
+                for axis in 0..3 {
+                    assert!(value[axis] >= 0.0 && value[axis] <= color[axis] + 1e-12);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn image_is_bilinearly_filtered() {
+        // Black on the left, white on the right.
+        let image = RgbImage::from_fn(2, 1, |x, _| Rgb([if x == 0 { 0 } else { 255 }; 3]));
+        let texture = ImageTexture::new(image, WrapMode::Clamp);
+
+        assert_eq!(texture.value(0.25, 0.5, origin()).x, 0.0);
+        assert_eq!(texture.value(0.75, 0.5, origin()).x, 1.0);
+        assert!((texture.value(0.5, 0.5, origin()).x - 0.5).abs() < 1e-12);
+        // Clamped past the edge, rather than blending with the far side.
+        assert_eq!(texture.value(1.0, 0.5, origin()).x, 1.0);
+    }
+
+    #[test]
+    fn wrap_modes() {
+        assert_eq!(WrapMode::Repeat.wrap(-1, 4), 3);
+        assert_eq!(WrapMode::Repeat.wrap(5, 4), 1);
+        assert_eq!(WrapMode::Mirror.wrap(-1, 4), 0);
+        assert_eq!(WrapMode::Mirror.wrap(5, 4), 2);
+        assert_eq!(WrapMode::Clamp.wrap(-1, 4), 0);
+        assert_eq!(WrapMode::Clamp.wrap(5, 4), 3);
+    }
+}
@@ -0,0 +1,149 @@
+// Tone mapping
+
+use super::RgbExt;
+use image::Rgb;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// How linear radiance, which can be arbitrarily bright, is squeezed into [0, 1] for 8-bit
+/// images.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMap {
+    /// Anything brighter than 1 is cut off.
+    #[default]
+    Clamp,
+    /// L / (1 + L) on luminance: never quite reaches white.
+    Reinhard,
+    /// Reinhard, scaled so luminance at the white point maps to exactly 1.
+    ExtendedReinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces,
+}
+
+impl ToneMap {
+    pub fn apply(&self, color: Rgb<f64>, white_point: f64) -> Rgb<f64> {
+        let mapped = match self {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMap::ExtendedReinhard => {
+                let white2 = white_point * white_point;
+
+                scale_luminance(color, |l| l * (1.0 + l / white2) / (1.0 + l))
+            }
+            ToneMap::Aces => {
+                let curve = |x: f64| (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+
+                Rgb([curve(color[0]), curve(color[1]), curve(color[2])])
+            }
+        };
+
+        Rgb([
+            mapped[0].clamp(0.0, 1.0),
+            mapped[1].clamp(0.0, 1.0),
+            mapped[2].clamp(0.0, 1.0),
+        ])
+    }
+}
+
+/// Applies a curve to the color's luminance, keeping its hue.
+fn scale_luminance(color: Rgb<f64>, curve: impl Fn(f64) -> f64) -> Rgb<f64> {
+    let luminance = color.luminance();
+    if luminance <= 0.0 {
+        return color;
+    }
+
+    color.multiply(curve(luminance) / luminance)
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "clamp" => Ok(ToneMap::Clamp),
+            "reinhard" => Ok(ToneMap::Reinhard),
+            "extended_reinhard" | "extended-reinhard" => Ok(ToneMap::ExtendedReinhard),
+            "aces" => Ok(ToneMap::Aces),
+            _ => Err(format!(
+                "unknown tone map \"{}\" (expected clamp, reinhard, extended-reinhard or aces)",
+                name
+            )),
+        }
+    }
+}
+
+/// Everything that happens to a linear pixel on its way into an 8-bit image.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OutputTransform {
+    pub tone_map: ToneMap,
+    /// In stops: each one doubles the brightness.
+    pub exposure: f64,
+    /// Luminance that the extended Reinhard operator maps to white.
+    pub white_point: f64,
+}
+
+impl Default for OutputTransform {
+    fn default() -> Self {
+        OutputTransform {
+            tone_map: ToneMap::Clamp,
+            exposure: 0.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+impl OutputTransform {
+    /// Exposure, then tone mapping, then the sRGB transfer curve.
+    pub fn apply(&self, color: Rgb<f64>) -> Rgb<f64> {
+        let exposed = color.multiply(2.0_f64.powf(self.exposure));
+
+        self.tone_map
+            .apply(exposed, self.white_point)
+            .linear_to_srgb()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators_stay_in_range_and_keep_black() {
+        for tone_map in [
+            ToneMap::Clamp,
+            ToneMap::Reinhard,
+            ToneMap::ExtendedReinhard,
+            ToneMap::Aces,
+        ] {
+            let black = tone_map.apply(Rgb([0.0, 0.0, 0.0]), 4.0);
+            assert!(black.0.iter().all(|c| c.abs() < 1e-3), "{:?}", tone_map);
+
+            for brightness in [0.01, 0.5, 1.0, 10.0, 1000.0] {
+                let mapped = tone_map.apply(Rgb([brightness, brightness * 0.5, 0.0]), 4.0);
+                assert!(mapped.0.iter().all(|c| (0.0..=1.0).contains(c)));
+            }
+        }
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_point_to_one() {
+        let white = ToneMap::ExtendedReinhard.apply(Rgb([4.0, 4.0, 4.0]), 4.0);
+        assert!(white.0.iter().all(|c| (c - 1.0).abs() < 1e-9));
+
+        let plain = ToneMap::Reinhard.apply(Rgb([1.0, 1.0, 1.0]), 4.0);
+        assert!(plain.0.iter().all(|c| (c - 0.5).abs() < 1e-9));
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let transform = OutputTransform {
+            exposure: 1.0,
+            ..Default::default()
+        };
+
+        let doubled = transform.apply(Rgb([0.25, 0.25, 0.25]));
+        let expected = OutputTransform::default().apply(Rgb([0.5, 0.5, 0.5]));
+        assert_eq!(doubled, expected);
+    }
+}
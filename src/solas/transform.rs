@@ -0,0 +1,175 @@
+// Transforms and instancing
+
+use super::*;
+use cgmath::{prelude::*, Deg, Matrix4, Vector3, Vector4};
+use std::sync::Arc;
+
+/// An object placed in the scene through an affine transform. The object itself is shared, so
+/// the same mesh (wrapped in its own `Bvh`) can be instanced any number of times without
+/// copying it.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    /// Object space to world space, and back.
+    to_world: Matrix4<f64>,
+    to_object: Matrix4<f64>,
+    bounds: Aabb,
+}
+
+impl Instance {
+    /// `transform` must be invertible, i.e. not scale anything to nothing.
+    pub fn new(object: Arc<dyn Hittable>, transform: Matrix4<f64>) -> Instance {
+        let to_object = transform
+            .invert()
+            .expect("instance transforms must be invertible");
+        let bounds = transform_bounds(&transform, &object.bounding_box());
+
+        Instance {
+            object,
+            to_world: transform,
+            to_object,
+            bounds,
+        }
+    }
+
+    /// The object as it is, to be moved with the methods below.
+    pub fn of(object: Arc<dyn Hittable>) -> Instance {
+        Instance::new(object, Matrix4::identity())
+    }
+
+    /// Applies `transform` after the instance's existing transform.
+    pub fn transform(self, transform: Matrix4<f64>) -> Instance {
+        Instance::new(self.object, transform * self.to_world)
+    }
+
+    pub fn translate(self, offset: Vector3<f64>) -> Instance {
+        self.transform(Matrix4::from_translation(offset))
+    }
+
+    /// Rotates counter-clockwise about `axis` (through the origin) when looking down it.
+    pub fn rotate(self, axis: Vector3<f64>, degrees: f64) -> Instance {
+        self.transform(Matrix4::from_axis_angle(axis.normalize(), Deg(degrees)))
+    }
+
+    /// Scales by a possibly different amount along each axis, about the origin.
+    pub fn scale(self, factors: Vector3<f64>) -> Instance {
+        self.transform(Matrix4::from_nonuniform_scale(
+            factors.x, factors.y, factors.z,
+        ))
+    }
+}
+
+fn point(matrix: &Matrix4<f64>, p: Vector3<f64>) -> Vector3<f64> {
+    (matrix * p.extend(1.0)).truncate()
+}
+
+fn direction(matrix: &Matrix4<f64>, d: Vector3<f64>) -> Vector3<f64> {
+    (matrix * d.extend(0.0)).truncate()
+}
+
+/// Box around all eight corners of `bounds` once transformed.
+fn transform_bounds(matrix: &Matrix4<f64>, bounds: &Aabb) -> Aabb {
+    let mut result = Aabb::empty();
+
+    for corner in 0..8 {
+        let pick = |axis: usize| {
+            if corner & (1 << axis) == 0 {
+                bounds.min[axis]
+            } else {
+                bounds.max[axis]
+            }
+        };
+        let p = point(matrix, Vector3::new(pick(0), pick(1), pick(2)));
+        result = result.surrounding(&Aabb::new(p, p));
+    }
+
+    result
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, min: f64, max: f64, sampler: &mut Sampler) -> Option<Hit<'_>> {
+        // The direction isn't renormalized, so t means the same thing in both spaces.
+        let local = Ray::with_time(
+            point(&self.to_object, ray.origin),
+            direction(&self.to_object, ray.direction),
+            ray.time,
+        );
+
+        let mut hit = self.object.hit(&local, min, max, sampler)?;
+
+        // Normals transform by the inverse transpose to stay perpendicular to the surface.
+        let normal = Vector4::new(hit.normal.x, hit.normal.y, hit.normal.z, 0.0);
+        hit.normal = (self.to_object.transpose() * normal).truncate().normalize();
+        hit.p = point(&self.to_world, hit.p);
+
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_sphere() -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            1.0,
+            make_lambertian(Vector3::new(0.5, 0.5, 0.5)),
+        ))
+    }
+
+    fn nearly_equal(a: Vector3<f64>, b: Vector3<f64>) -> bool {
+        (a - b).magnitude() < 1e-9
+    }
+
+    #[test]
+    fn translated_and_scaled_sphere() {
+        // An ellipsoid two wide in x, centered at (5, 0, 0).
+        let instance = Instance::of(unit_sphere())
+            .scale(Vector3::new(2.0, 1.0, 1.0))
+            .translate(Vector3::new(5.0, 0.0, 0.0));
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let hit = instance.hit(&ray, 0.001, 10000.0, &mut sampler).unwrap();
+        assert!((hit.t - 3.0).abs() < 1e-9);
+        assert!(nearly_equal(hit.p, Vector3::new(3.0, 0.0, 0.0)));
+        assert!(nearly_equal(hit.normal, Vector3::new(-1.0, 0.0, 0.0)));
+
+        // Normals of a stretched sphere aren't just stretched radii.
+        let p = Vector3::new(5.0 + 2.0_f64.sqrt(), 0.5_f64.sqrt(), 0.0);
+        let expected = Vector3::new(0.5_f64.sqrt() / 2.0, 0.5_f64.sqrt(), 0.0).normalize();
+        let ray = Ray::new(p + expected * 3.0, -expected);
+        let hit = instance.hit(&ray, 0.001, 10000.0, &mut sampler).unwrap();
+        assert!(nearly_equal(hit.p, p));
+        assert!(nearly_equal(hit.normal, expected));
+
+        let bounds = instance.bounding_box();
+        assert!(nearly_equal(bounds.min, Vector3::new(3.0, -1.0, -1.0)));
+        assert!(nearly_equal(bounds.max, Vector3::new(7.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn rotated_quad() {
+        // Facing +z, turned a quarter about y to face +x.
+        let quad: Arc<dyn Hittable> = Arc::new(Quad::xy_rect(
+            -1.0,
+            1.0,
+            -1.0,
+            1.0,
+            0.0,
+            make_lambertian(Vector3::new(0.5, 0.5, 0.5)),
+        ));
+        let instance = Instance::of(quad).rotate(Vector3::new(0.0, 1.0, 0.0), 90.0);
+
+        let ray = Ray::new(Vector3::new(5.0, 0.5, 0.5), Vector3::new(-1.0, 0.0, 0.0));
+        let hit = instance
+            .hit(&ray, 0.001, 10000.0, &mut Sampler::new(DEFAULT_SEED, 0))
+            .unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-9);
+        assert!(nearly_equal(hit.normal, Vector3::new(1.0, 0.0, 0.0)));
+    }
+}
@@ -0,0 +1,159 @@
+// Participating media
+
+use super::*;
+use cgmath::{prelude::*, Vector3};
+use std::sync::Arc;
+
+/// Distance a ray travels through a medium of the given density before it scatters, drawn
+/// from the exponential distribution.
+fn scatter_distance(density: f64, sampler: &mut Sampler) -> f64 {
+    -(1.0 - sampler.next_f64()).ln() / density
+}
+
+/// Smoke, fog or mist of the same density everywhere inside `boundary`, which must be a
+/// closed shape such as a sphere or a box. Rays passing through may scatter at any point
+/// inside it, in a direction chosen by the `phase` material (usually isotropic).
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable>,
+    density: f64,
+    phase: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: impl Hittable + 'static,
+        density: f64,
+        phase: Arc<dyn Material>,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary: Box::new(boundary),
+            density,
+            phase,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, min: f64, max: f64, sampler: &mut Sampler) -> Option<Hit<'_>> {
+        // Where the ray's line enters and leaves the boundary, even if that's behind it.
+        let entry = self
+            .boundary
+            .hit(ray, f64::NEG_INFINITY, f64::INFINITY, sampler)?;
+        let exit = self
+            .boundary
+            .hit(ray, entry.t + 0.0001, f64::INFINITY, sampler)?;
+
+        let enter = entry.t.max(min).max(0.0);
+        let leave = exit.t.min(max);
+        if enter >= leave {
+            return None;
+        }
+
+        let speed = ray.direction.magnitude();
+        let distance = scatter_distance(self.density, sampler);
+        if distance > (leave - enter) * speed {
+            return None;
+        }
+
+        let t = enter + distance / speed;
+
+        // The normal is meaningless inside a volume.
+        Some(Hit::new(
+            t,
+            ray.point(t),
+            Vector3::new(1.0, 0.0, 0.0),
+            (0.0, 0.0),
+            self.phase.as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Homogeneous fog filling the space between the scene's surfaces. Rays that leave the scene
+/// without hitting anything pass through unaffected, so the background stays visible.
+pub struct Fog {
+    density: f64,
+    phase: Arc<dyn Material>,
+}
+
+impl Fog {
+    pub fn new(density: f64, color: Vector3<f64>) -> Fog {
+        Fog {
+            density,
+            phase: make_isotropic(color),
+        }
+    }
+
+    /// Where a ray that would otherwise reach a surface at `max` scatters in the fog, if it
+    /// does.
+    pub fn hit(&self, ray: &Ray, max: f64, sampler: &mut Sampler) -> Option<Hit<'_>> {
+        let speed = ray.direction.magnitude();
+        let t = scatter_distance(self.density, sampler) / speed;
+        if t >= max {
+            return None;
+        }
+
+        Some(Hit::new(
+            t,
+            ray.point(t),
+            Vector3::new(1.0, 0.0, 0.0),
+            (0.0, 0.0),
+            self.phase.as_ref(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dense_medium_scatters_near_the_boundary() {
+        let medium = ConstantMedium::new(
+            Sphere::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                1.0,
+                make_lambertian(Vector3::new(0.5, 0.5, 0.5)),
+            ),
+            1e6,
+            make_isotropic(Vector3::new(1.0, 1.0, 1.0)),
+        );
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -2.0));
+        let hit = medium.hit(&ray, 0.001, 10000.0, &mut sampler).unwrap();
+        assert!(hit.t >= 2.0 && hit.t < 2.001);
+
+        // Starting inside the volume.
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = medium.hit(&ray, 0.001, 10000.0, &mut sampler).unwrap();
+        assert!(hit.t < 0.01);
+
+        let miss = Ray::new(Vector3::new(0.0, 2.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+        assert!(medium.hit(&miss, 0.001, 10000.0, &mut sampler).is_none());
+    }
+
+    #[test]
+    fn thin_medium_lets_most_rays_through() {
+        let medium = ConstantMedium::new(
+            Cuboid::new(
+                Vector3::new(-1.0, -1.0, -1.0),
+                Vector3::new(1.0, 1.0, 1.0),
+                make_lambertian(Vector3::new(0.5, 0.5, 0.5)),
+            ),
+            0.1,
+            make_isotropic(Vector3::new(1.0, 1.0, 1.0)),
+        );
+        let mut sampler = Sampler::new(DEFAULT_SEED, 0);
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+
+        // Transmittance through 2 units at density 0.1 is exp(-0.2), about 82%.
+        let scattered = (0..10000)
+            .filter(|_| medium.hit(&ray, 0.001, 10000.0, &mut sampler).is_some())
+            .count();
+        assert!((1600..2000).contains(&scattered), "{}", scattered);
+    }
+}